@@ -1,13 +1,16 @@
 use crate::{
     object::{
-        d2::{Node2d, ON_TOUCH},
+        d2::{Node2d, ON_TOUCH, UPDATE_FRAME},
+        gesture::{self, GestureConfig, GESTURE_CONFIG},
         Touch,
     },
     prelude::CreateNode2d,
     render::{
+        atlas::ATLAS,
         d2::{
             upd_proj, CANVAS, CANVAS_UPDATE, RENDERS,
         },
+        light::{OCCLUDERS, SHADOWS_UPDATE},
         Rgba, FPS, FPS_BUFFER, LAST_FPS_TIME, LAST_FRAME_TIME, WINDOW, WINDOW_UPDATE,
     },
 };
@@ -19,6 +22,7 @@ use crate::render::miniquad::render;
 use crate::render::wgpu::render;
 
 use glam::{vec2, Vec2};
+use std::mem::take;
 
 pub const BLACK: Rgba = Rgba::new(0., 0., 0., 1.0);
 
@@ -35,10 +39,28 @@ pub(crate) static mut MOUSE: Vec2 = Vec2::new(0., 0.);
 pub(crate) static mut MOUSE_DELTA: Vec2 = Vec2::new(0., 0.);
 pub(crate) static mut MOUSE_WHEEL_DELTA: Vec2 = Vec2::new(0., 0.);
 
+/// Позиция мыши в координатах мира.
+#[inline(always)]
+pub fn get_mouse() -> Vec2 {
+    unsafe { MOUSE }
+}
+
 pub(crate) fn update() {
     unsafe {
+        // Якоря и keep должны видеть новый размер холста уже в этом кадре
+        if CANVAS_UPDATE {
+            upd_proj();
+        }
+
         if let Some(node) = &mut NODE2D {
+            for g in gesture::update() {
+                node.gesture(&g);
+            }
+
+            let last = take(&mut OCCLUDERS);
             node.update();
+            SHADOWS_UPDATE = OCCLUDERS != last;
+            UPDATE_FRAME += 1;
         }
     }
 }
@@ -68,6 +90,8 @@ pub(crate) fn draw() {
 }
 
 pub(crate) fn touch(id: u64, touch: &Touch, pos: Vec2) {
+    gesture::feed(id, touch, pos);
+
     unsafe {
         if let Some(node) = &mut NODE2D {
             ON_TOUCH = true;
@@ -147,6 +171,23 @@ impl Engine {
         self
     }
 
+    pub fn gestures(self, config: GestureConfig) -> Self {
+        unsafe {
+            GESTURE_CONFIG = config;
+        }
+        self
+    }
+
+    /// Автоматическая упаковка текстур до 512 пикселей в атлас (включена по умолчанию).
+    /// Меньше смен текстур при отрисовке, но id текстур становятся общими со страницей.
+    /// `atlas(false)` оставляет каждой картинке свою текстуру.
+    pub fn atlas(self, sel: bool) -> Self {
+        unsafe {
+            ATLAS = sel;
+        }
+        self
+    }
+
     pub fn backgraund(self, color: Rgba) -> Self {
         unsafe {
             BACKGRAUND = color;
@@ -2,6 +2,7 @@
 pub mod data;
 pub mod engine;
 pub mod object;
+pub(crate) mod parse;
 pub mod physic;
 pub mod prelude;
 pub mod render;
@@ -0,0 +1,571 @@
+use super::{
+    d2::NineSlice,
+    sprite::{Playback, SpriteSheet},
+};
+use crate::{
+    data::load_file,
+    parse::{self, dir, join, Json},
+    render::{new_texture, try_texture, Texture},
+};
+
+use glam::{vec2, Vec2};
+use std::collections::HashMap;
+
+/// Именованная область спрайта из Aseprite.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Slice {
+    pub texture: Texture,
+    /// Отступы 9-slice в пикселях: слева, сверху, справа, снизу.
+    pub border: Option<[f32; 4]>,
+    /// Точка опоры от левого верхнего угла области.
+    pub pivot: Option<Vec2>,
+}
+
+impl Slice {
+    /// Готовый `NineSlice`, если у области задан центр.
+    pub fn nine_slice(&self) -> Option<NineSlice> {
+        let [left, up, right, down] = self.border?;
+        Some(NineSlice::new(&self.texture, left, up, right, down))
+    }
+}
+
+/// Спрайт из Aseprite: лист кадров с анимациями по тегам и области (slices).
+#[derive(Clone)]
+pub struct Aseprite {
+    pub sheet: SpriteSheet,
+    /// Вся текстура листа.
+    pub texture: Texture,
+    pub slices: HashMap<String, Slice>,
+}
+
+impl Aseprite {
+    pub fn slice(&self, name: &str) -> Option<&Slice> {
+        self.slices.get(name)
+    }
+}
+
+/// Тег анимации: кадры from..=to, направление и число повторов (0 - бесконечно).
+struct Tag {
+    name: String,
+    from: usize,
+    to: usize,
+    direction: u8,
+    repeat: u32,
+}
+
+/// Область на первом кадре: границы, центр 9-slice и опора.
+struct SliceKey {
+    name: String,
+    bounds: [f32; 4],
+    center: Option<[f32; 4]>,
+    pivot: Option<Vec2>,
+}
+
+/// Загружает файл .aseprite/.ase или JSON-экспорт листа из Aseprite через `load_file`.
+///
+/// Теги становятся анимациями `SpriteSheet` с длительностями кадров,
+/// обратные и ping-pong теги учитываются, тег с одним повтором играет `Once`.
+/// Области берутся с первого кадра. В бинарном файле видимые слои
+/// сводятся обычным наложением, режимы смешивания слоёв не учитываются.
+/// В JSON-экспорте кадры должны быть без поворота, обрезка (trim) не возвращается.
+pub fn aseprite(path: &str) -> Result<Aseprite, String> {
+    let bytes = load_file(path).map_err(|e| format!("{path}: {e}"))?;
+
+    if bytes.get(4..6) == Some(&[0xE0, 0xA5]) {
+        binary(&bytes).map_err(|e| format!("{path}: {e}"))
+    } else {
+        let text = String::from_utf8(bytes).map_err(|_| format!("{path}: not UTF-8"))?;
+        sheet(&parse::json(&text)?, dir(path))
+    }
+}
+
+fn build(texture: Texture, frames: &[(f32, f32, f32, f32)], durations: &[f32], tags: &[Tag], slices: &[SliceKey]) -> Aseprite {
+    let mut sheet = SpriteSheet::frames(&texture, frames);
+
+    for tag in tags {
+        let to = tag.to.min(frames.len().saturating_sub(1));
+        if tag.from > to {
+            continue;
+        }
+
+        let mut list: Vec<(usize, f32)> = (tag.from..=to).map(|f| (f, durations[f] / 1000.)).collect();
+
+        // 1 - обратно, 3 - ping-pong с конца
+        if tag.direction == 1 || tag.direction == 3 {
+            list.reverse();
+        }
+
+        let playback = match (tag.direction, tag.repeat) {
+            (2 | 3, _) => Playback::PingPong,
+            (_, 1) => Playback::Once,
+            _ => Playback::Loop,
+        };
+
+        sheet = sheet.animation_timed(&tag.name, &list, playback);
+    }
+
+    let origin = frames.first().copied().unwrap_or_default();
+
+    let slices = slices
+        .iter()
+        .map(|s| {
+            let [x, y, w, h] = s.bounds;
+
+            let border = s.center.map(|[cx, cy, cw, ch]| [cx, cy, w - cx - cw, h - cy - ch]);
+
+            let slice = Slice {
+                texture: texture.region(origin.0 + x, origin.1 + y, w, h),
+                border,
+                pivot: s.pivot,
+            };
+
+            (s.name.clone(), slice)
+        })
+        .collect();
+
+    Aseprite {
+        sheet,
+        texture,
+        slices,
+    }
+}
+
+/// JSON-экспорт листа: кадры массивом или объектом, теги и области в `meta`.
+fn sheet(j: &Json, dir: &str) -> Result<Aseprite, String> {
+    let meta = j.get("meta");
+    let image = meta.get("image").str().ok_or("Aseprite: no meta.image")?;
+    let texture = try_texture(&join(dir, image))?;
+
+    let list: Vec<&Json> = match j.get("frames") {
+        Json::Object(frames) => frames.iter().map(|(_, f)| f).collect(),
+        frames => frames.array().iter().collect(),
+    };
+
+    if list.is_empty() {
+        return Err("Aseprite: no frames".to_string());
+    }
+
+    let mut frames = Vec::new();
+    let mut durations = Vec::new();
+
+    for f in list {
+        if f.get("rotated").bool() == Some(true) {
+            return Err("Aseprite: rotated frames are not supported".to_string());
+        }
+
+        frames.push(rect(f.get("frame")).into());
+        durations.push(f.get("duration").f32().unwrap_or(100.));
+    }
+
+    let tags: Vec<Tag> = meta
+        .get("frameTags")
+        .array()
+        .iter()
+        .map(|t| Tag {
+            name: t.get("name").str().unwrap_or_default().to_string(),
+            from: t.get("from").u32().unwrap_or(0) as usize,
+            to: t.get("to").u32().unwrap_or(0) as usize,
+            direction: match t.get("direction").str() {
+                Some("reverse") => 1,
+                Some("pingpong") => 2,
+                Some("pingpong_reverse") => 3,
+                _ => 0,
+            },
+            // Новые версии пишут повторы строкой
+            repeat: match t.get("repeat") {
+                Json::String(s) => s.parse().unwrap_or(0),
+                r => r.u32().unwrap_or(0),
+            },
+        })
+        .collect();
+
+    let slices: Vec<SliceKey> = meta
+        .get("slices")
+        .array()
+        .iter()
+        .filter_map(|s| {
+            let key = s.get("keys").array().first()?;
+
+            Some(SliceKey {
+                name: s.get("name").str().unwrap_or_default().to_string(),
+                bounds: rect(key.get("bounds")),
+                center: (!key.get("center").is_null()).then(|| rect(key.get("center"))),
+                pivot: (!key.get("pivot").is_null()).then(|| {
+                    vec2(
+                        key.get("pivot").get("x").f32().unwrap_or(0.),
+                        key.get("pivot").get("y").f32().unwrap_or(0.),
+                    )
+                }),
+            })
+        })
+        .collect();
+
+    Ok(build(texture, &frames, &durations, &tags, &slices))
+}
+
+fn rect(j: &Json) -> [f32; 4] {
+    ["x", "y", "w", "h"].map(|k| j.get(k).f32().unwrap_or(0.))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    i: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let bytes = self.data.get(self.i..self.i + n).ok_or("unexpected end of file")?;
+        self.i += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn i16(&mut self) -> Result<i16, String> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(self.u32()? as i32)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+}
+
+struct Layer {
+    visible: bool,
+    /// Обычный слой с картинками (не группа и не тайлы).
+    image: bool,
+    opacity: u8,
+    background: bool,
+}
+
+#[derive(Clone)]
+struct Cel {
+    layer: usize,
+    x: i32,
+    y: i32,
+    opacity: u8,
+    z: i32,
+    width: usize,
+    height: usize,
+    /// RGBA.
+    pixels: Vec<u8>,
+}
+
+/// Бинарный .aseprite: слои сводятся в кадры, кадры - в одну текстуру сеткой.
+fn binary(data: &[u8]) -> Result<Aseprite, String> {
+    let mut r = Reader { data, i: 4 };
+
+    r.u16()?;
+    let count = r.u16()? as usize;
+    let width = r.u16()? as usize;
+    let height = r.u16()? as usize;
+    let depth = r.u16()?;
+    let flags = r.u32()?;
+    r.bytes(10)?;
+    let transparent = r.u8()?;
+    r.i = 128;
+
+    if ![8, 16, 32].contains(&depth) {
+        return Err(format!("unsupported color depth {depth}"));
+    }
+
+    let mut layers: Vec<Layer> = Vec::new();
+    // Видимость групп по уровню вложенности
+    let mut groups: Vec<bool> = Vec::new();
+    let mut palette = vec![[0u8; 4]; 256];
+    let mut tags = Vec::new();
+    let mut slices = Vec::new();
+    let mut durations = Vec::new();
+    let mut cels: Vec<Vec<Cel>> = Vec::new();
+
+    for frame in 0..count {
+        let start = r.i;
+        let size = r.u32()? as usize;
+        if r.u16()? != 0xF1FA {
+            return Err(format!("bad frame {frame}"));
+        }
+
+        let old = r.u16()? as usize;
+        durations.push(r.u16()? as f32);
+        r.bytes(2)?;
+        let chunks = match r.u32()? as usize {
+            0 => old,
+            n => n,
+        };
+
+        let mut frame_cels: Vec<Cel> = Vec::new();
+
+        for _ in 0..chunks {
+            let chunk = r.i;
+            let size = r.u32()? as usize;
+            let kind = r.u16()?;
+
+            match kind {
+                0x2004 => {
+                    let layer_flags = r.u16()?;
+                    let layer_type = r.u16()?;
+                    let level = r.u16()? as usize;
+                    r.bytes(6)?;
+                    let opacity = r.u8()?;
+
+                    groups.truncate(level);
+                    let parent = groups.last().copied().unwrap_or(true);
+                    // Скрытые и референсные слои не попадают в кадр
+                    let visible = parent && layer_flags & 1 != 0 && layer_flags & 64 == 0;
+                    groups.push(visible);
+
+                    layers.push(Layer {
+                        visible,
+                        image: layer_type == 0,
+                        opacity: if flags & 1 != 0 { opacity } else { 255 },
+                        background: layer_flags & 8 != 0,
+                    });
+                }
+                0x2005 => {
+                    let layer = r.u16()? as usize;
+                    let x = r.i16()? as i32;
+                    let y = r.i16()? as i32;
+                    let opacity = r.u8()?;
+                    let cel_type = r.u16()?;
+                    let z = r.i16()? as i32;
+                    r.bytes(5)?;
+
+                    match cel_type {
+                        0 | 2 => {
+                            let w = r.u16()? as usize;
+                            let h = r.u16()? as usize;
+                            let raw = data.get(r.i..(chunk + size).min(data.len())).ok_or("bad cel chunk")?;
+                            let raw = if cel_type == 2 { parse::inflate(raw, "zlib")? } else { raw.to_vec() };
+
+                            let background = layers.get(layer).is_some_and(|l| l.background);
+                            let pixels = pixels(&raw, w * h, depth, &palette, transparent, background)?;
+
+                            frame_cels.push(Cel {
+                                layer,
+                                x,
+                                y,
+                                opacity,
+                                z,
+                                width: w,
+                                height: h,
+                                pixels,
+                            });
+                        }
+                        1 => {
+                            let link = r.u16()? as usize;
+                            if let Some(cel) = cels.get(link).and_then(|c| c.iter().find(|c| c.layer == layer)) {
+                                frame_cels.push(cel.clone());
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                0x0004 => {
+                    let mut index = 0;
+                    for _ in 0..r.u16()? {
+                        index += r.u8()? as usize;
+                        let n = match r.u8()? {
+                            0 => 256,
+                            n => n as usize,
+                        };
+                        for _ in 0..n {
+                            let c = r.bytes(3)?;
+                            if let Some(p) = palette.get_mut(index) {
+                                *p = [c[0], c[1], c[2], 255];
+                            }
+                            index += 1;
+                        }
+                    }
+                }
+                0x2019 => {
+                    r.u32()?;
+                    let first = r.u32()? as usize;
+                    let last = r.u32()? as usize;
+                    r.bytes(8)?;
+
+                    if first > last || last >= 256 {
+                        return Err(format!("palette range {first}..={last}"));
+                    }
+
+                    for index in first..=last {
+                        let named = r.u16()? & 1 != 0;
+                        let c = r.bytes(4)?;
+                        if named {
+                            r.string()?;
+                        }
+
+                        if index >= palette.len() {
+                            palette.resize(index + 1, [0; 4]);
+                        }
+                        palette[index] = [c[0], c[1], c[2], c[3]];
+                    }
+                }
+                0x2018 => {
+                    let n = r.u16()?;
+                    r.bytes(8)?;
+
+                    for _ in 0..n {
+                        let from = r.u16()? as usize;
+                        let to = r.u16()? as usize;
+                        let direction = r.u8()?;
+                        let repeat = r.u16()? as u32;
+                        r.bytes(10)?;
+
+                        tags.push(Tag {
+                            name: r.string()?,
+                            from,
+                            to,
+                            direction,
+                            repeat,
+                        });
+                    }
+                }
+                0x2022 => {
+                    let keys = r.u32()?;
+                    let slice_flags = r.u32()?;
+                    r.u32()?;
+                    let name = r.string()?;
+
+                    for key in 0..keys {
+                        let _frame = r.u32()?;
+                        let bounds = [r.i32()? as f32, r.i32()? as f32, r.u32()? as f32, r.u32()? as f32];
+                        let center = match slice_flags & 1 {
+                            0 => None,
+                            _ => Some([r.i32()? as f32, r.i32()? as f32, r.u32()? as f32, r.u32()? as f32]),
+                        };
+                        let pivot = match slice_flags & 2 {
+                            0 => None,
+                            _ => Some(vec2(r.i32()? as f32, r.i32()? as f32)),
+                        };
+
+                        if key == 0 {
+                            slices.push(SliceKey {
+                                name: name.clone(),
+                                bounds,
+                                center,
+                                pivot,
+                            });
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            r.i = chunk + size.max(6);
+        }
+
+        cels.push(frame_cels);
+        r.i = start + size;
+    }
+
+    if count == 0 {
+        return Err("no frames".to_string());
+    }
+
+    // Кадры сеткой, близкой к квадрату
+    let columns = (count as f32).sqrt().ceil() as usize;
+    let rows = count.div_ceil(columns);
+    let stride = width * columns;
+    let len = stride
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(rows))
+        .and_then(|n| n.checked_mul(4))
+        .ok_or_else(|| format!("sheet {width}x{height} with {count} frames is too large"))?;
+    let mut atlas = vec![0u8; len];
+    let mut frames = Vec::with_capacity(count);
+
+    for (frame, list) in cels.iter_mut().enumerate() {
+        let ox = frame % columns * width;
+        let oy = frame / columns * height;
+        frames.push((ox as f32, oy as f32, width as f32, height as f32));
+
+        // Порядок: слой плюс z-индекс, при равенстве меньший z ниже
+        list.sort_by_key(|c| (c.layer as i32 + c.z, c.z));
+
+        for cel in list.iter() {
+            let Some(layer) = layers.get(cel.layer) else { continue };
+            if !layer.visible || !layer.image {
+                continue;
+            }
+
+            let opacity = cel.opacity as f32 / 255. * layer.opacity as f32 / 255.;
+
+            for cy in 0..cel.height {
+                let y = cel.y + cy as i32;
+                if y < 0 || y >= height as i32 {
+                    continue;
+                }
+
+                for cx in 0..cel.width {
+                    let x = cel.x + cx as i32;
+                    if x < 0 || x >= width as i32 {
+                        continue;
+                    }
+
+                    let src = &cel.pixels[(cy * cel.width + cx) * 4..][..4];
+                    let i = ((oy + y as usize) * stride + ox + x as usize) * 4;
+                    over(&mut atlas[i..i + 4], src, opacity);
+                }
+            }
+        }
+    }
+
+    let texture = new_texture(atlas, stride as u32, (height * rows) as u32);
+    Ok(build(texture, &frames, &durations, &tags, &slices))
+}
+
+/// Пиксели ячейки в RGBA из глубины цвета файла.
+fn pixels(raw: &[u8], count: usize, depth: u16, palette: &[[u8; 4]], transparent: u8, background: bool) -> Result<Vec<u8>, String> {
+    let size = depth as usize / 8;
+    if raw.len() < count * size {
+        return Err("cel data is too short".to_string());
+    }
+
+    let mut out = Vec::with_capacity(count * 4);
+
+    for p in raw.chunks_exact(size).take(count) {
+        match depth {
+            32 => out.extend_from_slice(p),
+            16 => out.extend_from_slice(&[p[0], p[0], p[0], p[1]]),
+            _ if p[0] == transparent && !background => out.extend_from_slice(&[0; 4]),
+            _ => out.extend_from_slice(palette.get(p[0] as usize).unwrap_or(&[0; 4])),
+        }
+    }
+
+    Ok(out)
+}
+
+/// Обычное наложение непремноженного RGBA с прозрачностью `opacity`.
+#[inline(always)]
+fn over(dst: &mut [u8], src: &[u8], opacity: f32) {
+    let sa = src[3] as f32 / 255. * opacity;
+    if sa <= 0. {
+        return;
+    }
+
+    let da = dst[3] as f32 / 255.;
+    let a = sa + da * (1. - sa);
+
+    for c in 0..3 {
+        let v = (src[c] as f32 * sa + dst[c] as f32 * da * (1. - sa)) / a;
+        dst[c] = v.round() as u8;
+    }
+    dst[3] = (a * 255.).round() as u8;
+}
@@ -1,22 +1,127 @@
-use super::{Keep, Touch};
-use crate::{prelude::{del_render, new_render}, render::{
+use super::{gesture::{Gesture, GestureMode}, particles::{Emitter, ParticleSpace, Particles}, prefab::Prefab, sprite::{Sprite, SpriteSheet}, tilemap::TileMap, Anchor, Axis, Drag, Hit, Input, Keep, Occluder, Pointer, Stretch, Touch};
+use crate::{info::DEVICE, prelude::{del_render, new_render}, render::{
     add_text,
-    d2::{draw, CAMERA2D, CANVAS_PROJ, CANVAS_UPDATE, RENDERS},
-    rgb, Font, Rgba, Texture, DELTA,
+    light::{Light, Shadow, LIGHTING, OCCLUDERS, SHADOWS_UPDATE},
+    material::Material,
+    shape::{self, Gradient, Line, Path, Stroke},
+    texture_alpha,
+    d2::{clear_render, draw, pixel_scale, Paint, view_rect, Render, RenderTarget, CAMERA2D, CANVAS, CANVAS_PROJ, CANVAS_UPDATE, RENDERS},
+    rgb, Blend, Font, Rgba, Texture, DELTA,
 }};
 
-use glam::{vec2, Vec2};
-use std::{any::Any, collections::HashMap, mem::take};
+use glam::{vec2, Affine2, Vec2};
+use std::{any::Any, collections::HashMap, mem::take, sync::Arc};
 
 pub(crate) static mut ON_TOUCH: bool = false;
+static mut PROPAGATION: bool = true;
+
+const GHOST: &str = "drag_ghost";
+
+/// Текущее перетаскивание: id узла, его призрака и откуда узел взяли.
+/// Узлы ищутся по id, поэтому правка дерева во время перетаскивания не сбивает их.
+struct Dragging {
+    id: u64,
+    node: u64,
+    start: Vec2,
+    grab: Vec2,
+    ghost: Option<u64>,
+    payload: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+static mut DRAG: Option<Dragging> = None;
+
+/// Наведение одного указателя: где он был и id узлов-приёмников под ним от корня.
+struct Hovering {
+    id: u64,
+    pos: Vec2,
+    frame: u64,
+    nodes: Vec<u64>,
+}
+
+static mut HOVER: Vec<Hovering> = Vec::new();
+/// Путь и id узлов последнего поиска цели, чтобы не выделять память на каждое событие.
+static mut PICK: Vec<usize> = Vec::new();
+static mut PICK_NODES: Vec<u64> = Vec::new();
+
+/// Кадр обновления дерева: после него цель наведения ищется заново.
+pub(crate) static mut UPDATE_FRAME: u64 = 0;
+
+static mut NODE_ID: u64 = 0;
+/// Слот геометрии ещё не выделен.
+const NO_RENDER: usize = usize::MAX;
+
+/// Останавливает погружение или всплытие текущего события касания.
+#[inline(always)]
+pub fn stop_propagation() {
+    unsafe {
+        PROPAGATION = false;
+    }
+}
 
 #[derive(Clone, PartialEq)]
 pub enum Obj2d {
     None,
     Rect(f32, f32, f32),
     Circle(f32),
+    /// Эллипс с полуосями (rx, ry).
+    Ellipse(f32, f32),
+    /// Дуга радиуса r от угла start до end (в радианах), линией `Line`.
+    Arc(f32, f32, f32, Line),
+    /// Сектор круга радиуса r от угла start до end.
+    Pie(f32, f32, f32),
+    /// Кольцо с внешним и внутренним радиусом.
+    Ring(f32, f32),
     Texture(Texture),
     Text(String, f32, usize, Texture),
+    Sprite(Sprite),
+    NineSlice(NineSlice),
+    /// Залитый многоугольник, точки в локальных координатах узла.
+    Polygon(Vec<Vec2>),
+    /// Ломаная толщиной и формой `Line`.
+    Polyline(Vec<Vec2>, Line),
+    /// Своя геометрия: вершины (позиция, цвет, uv текстуры) и треугольники.
+    /// Меняется через `Node2d::mesh` и `Node2d::set_mesh`: правка `obj`
+    /// напрямую не перерисовывает узел, пока он уже меш.
+    Mesh {
+        vertices: Vec<(Vec2, Rgba, Vec2)>,
+        indices: Vec<u16>,
+        texture: Option<Texture>,
+    },
+    Particles(Particles),
+    TileMap(TileMap),
+    /// Источник света, рисуется в буфер света сцены.
+    Light(Light),
+    /// Наложение буфера света на кадр.
+    Lighting(RenderTarget),
+}
+
+/// Текстура, растягиваемая без искажения углов.
+/// `border` - отступы в пикселях текстуры: (слева, сверху, справа, снизу).
+#[derive(Clone, Copy, PartialEq)]
+pub struct NineSlice {
+    pub texture: Texture,
+    pub width: f32,
+    pub height: f32,
+    pub border: [f32; 4],
+    /// Повторять края и центр вместо растягивания.
+    pub tile: bool,
+}
+
+impl NineSlice {
+    pub fn new(texture: &Texture, left: f32, up: f32, right: f32, down: f32) -> Self {
+        Self {
+            texture: *texture,
+            width: texture.width,
+            height: texture.height,
+            border: [left, up, right, down],
+            tile: false,
+        }
+    }
+
+    pub fn tile(mut self, tile: bool) -> Self {
+        self.tile = tile;
+        self
+    }
 }
 
 impl Obj2d {
@@ -24,15 +129,149 @@ impl Obj2d {
         if let Obj2d::Text(text, size, id, texture) = self {
             *text = new_text.to_string();
 
-            let (tex_id, w, h) = add_text(text, *size, *id, Some(texture.id));
-
-            texture.id = tex_id;
-            texture.width = w;
-            texture.height = h;
+            *texture = add_text(text, *size, *id, Some(texture));
         } else {
             panic!("Not a Text object!")
         }
     }
+
+    pub fn play(&mut self, animation: &str) {
+        self.sprite().play(animation);
+    }
+
+    pub fn queue(&mut self, animation: &str) {
+        self.sprite().queue(animation);
+    }
+
+    pub fn stop(&mut self) {
+        self.sprite().stop();
+    }
+
+    /// Текстура объекта, для отражения и прокрутки uv из скрипта.
+    pub fn texture(&mut self) -> &mut Texture {
+        match self {
+            Obj2d::Texture(t)
+            | Obj2d::Text(_, _, _, t)
+            | Obj2d::Sprite(Sprite { texture: t, .. })
+            | Obj2d::Mesh { texture: Some(t), .. } => t,
+            _ => panic!("Not a Texture object!"),
+        }
+    }
+
+    pub fn particles(&mut self) -> &mut Particles {
+        if let Obj2d::Particles(particles) = self {
+            particles
+        } else {
+            panic!("Not a Particles object!")
+        }
+    }
+
+    pub fn tilemap(&mut self) -> &mut TileMap {
+        if let Obj2d::TileMap(map) = self {
+            map
+        } else {
+            panic!("Not a TileMap object!")
+        }
+    }
+
+    pub fn sprite(&mut self) -> &mut Sprite {
+        if let Obj2d::Sprite(sprite) = self {
+            sprite
+        } else {
+            panic!("Not a Sprite object!")
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        match self {
+            Obj2d::Rect(w, h, _) | Obj2d::NineSlice(NineSlice { width: w, height: h, .. }) => {
+                vec2(*w, *h)
+            }
+            Obj2d::Circle(r) | Obj2d::Pie(r, _, _) | Obj2d::Ring(r, _) => vec2(*r, *r) * 2.,
+            Obj2d::Ellipse(rx, ry) => vec2(*rx, *ry) * 2.,
+            Obj2d::Arc(r, _, _, line) => Vec2::splat(r * 2. + line.width),
+            Obj2d::Texture(t)
+            | Obj2d::Text(_, _, _, t)
+            | Obj2d::Sprite(Sprite { texture: t, .. }) => vec2(t.width, t.height),
+            Obj2d::Polygon(points) => extent(points) * 2.,
+            Obj2d::Polyline(points, line) => extent(points) * 2. + line.width,
+            Obj2d::Mesh { vertices, .. } => {
+                vertices.iter().fold(Vec2::ZERO, |e, (p, _, _)| e.max(p.abs())) * 2.
+            }
+            Obj2d::Particles(p) => match p.emitter {
+                Emitter::Point => Vec2::splat(p.size),
+                Emitter::Circle(r) => Vec2::splat(r * 2. + p.size),
+                Emitter::Rect(w, h) => vec2(w, h) + p.size,
+            },
+            Obj2d::TileMap(map) => map.extent * 2.,
+            Obj2d::Light(light) => Vec2::splat(light.radius * 2.),
+            Obj2d::Lighting(_) | Obj2d::None => Vec2::ZERO,
+        }
+    }
+
+    /// Меш, частицы и карта перестраиваются по флагу, а не сравнением.
+    /// Для них - адрес и длина основного буфера: другой буфер значит,
+    /// что объект заменили целиком.
+    fn live(&self) -> Option<(usize, usize)> {
+        match self {
+            Obj2d::Mesh { vertices, .. } => Some((vertices.as_ptr() as usize, vertices.len())),
+            Obj2d::Particles(p) => Some((p.particles.as_ptr() as usize, p.particles.len())),
+            Obj2d::TileMap(map) => Some((map.visible.as_ptr() as usize, map.visible.len())),
+            _ => None,
+        }
+    }
+
+    /// Текстура, по uv которой строится геометрия.
+    fn main_texture(&mut self) -> Option<&mut Texture> {
+        match self {
+            Obj2d::Texture(t)
+            | Obj2d::Text(_, _, _, t)
+            | Obj2d::Sprite(Sprite { texture: t, .. })
+            | Obj2d::NineSlice(NineSlice { texture: t, .. })
+            | Obj2d::Mesh { texture: Some(t), .. } => Some(t),
+            Obj2d::Particles(p) => p.texture.as_mut(),
+            Obj2d::TileMap(map) => Some(&mut map.texture),
+            _ => None,
+        }
+    }
+
+    /// Id текстуры, с которой рисуется объект.
+    fn texture_id(&self) -> Option<usize> {
+        match self {
+            Obj2d::Rect(_, _, _) => None,
+            Obj2d::Circle(_)
+            | Obj2d::Ellipse(_, _)
+            | Obj2d::Arc(_, _, _, _)
+            | Obj2d::Pie(_, _, _)
+            | Obj2d::Ring(_, _) => None,
+            Obj2d::Polygon(_) | Obj2d::Polyline(_, _) => None,
+            Obj2d::Mesh { texture, .. } => texture.map(|t| t.id),
+            Obj2d::Particles(p) => p.texture.map(|t| t.id),
+            Obj2d::TileMap(map) => Some(map.texture.id),
+            Obj2d::Lighting(target) => Some(target.texture().id),
+            Obj2d::Light(_) => None,
+            Obj2d::Texture(t)
+            | Obj2d::Text(_, _, _, t)
+            | Obj2d::Sprite(Sprite { texture: t, .. })
+            | Obj2d::NineSlice(NineSlice { texture: t, .. }) => Some(t.id),
+            Obj2d::None => None,
+        }
+    }
+
+    /// Радиус скругления, по которому выбирается число отрезков.
+    fn radius(&self) -> f32 {
+        match self {
+            Obj2d::Rect(_, _, r) | Obj2d::Circle(r) | Obj2d::Pie(r, _, _) | Obj2d::Ring(r, _) => *r,
+            Obj2d::Ellipse(rx, ry) => rx.max(*ry),
+            Obj2d::Arc(r, _, _, line) => r + line.width / 2.,
+            _ => 0.,
+        }
+    }
+}
+
+/// Наибольшее удаление точек от центра узла по осям.
+fn extent(points: &[Vec2]) -> Vec2 {
+    points.iter().fold(Vec2::ZERO, |e, p| e.max(p.abs()))
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -44,18 +283,35 @@ pub(crate) enum DrawUpdate {
 
 struct Chache {
     offset: Vec2,
+    size: Vec2,
+    /// Отрезков на полный круг у скруглённых фигур.
+    detail: usize,
+    /// Геометрия меша, частиц или карты изменилась, `obj` не сравнивается.
+    dirty: bool,
     visible: bool,
+    subtree: bool,
 }
 
 struct Hidden {
+    /// Копия для сравнения, у меша, частиц и карты - `Obj2d::None`.
     obj: Obj2d,
-    parrent_position: Vec2,
+    live: Option<(usize, usize)>,
+    anchor: Option<Anchor>,
+    parent_size: Vec2,
+    parent: Affine2,
     global_position: Vec2,
+    global_rotation: f32,
+    global_scale: Vec2,
     position: Vec2,
     rotation: f32,
     scale: Vec2,
+    pivot: Vec2,
     offset: Vec2,
+    stroke: Option<Stroke>,
+    gradient: Option<Gradient>,
     visible: bool,
+    culled: bool,
+    subtree: bool,
 }
 
 pub struct Node2d {
@@ -66,20 +322,54 @@ pub struct Node2d {
     pub position: Vec2,
     pub rotation: f32,
     pub scale: Vec2,
+    pub global_rotation: f32,
+    pub global_scale: Vec2,
+    pub pivot: Vec2,
     pub color: Rgba,
     pub keep: Keep,
+    pub input: Input,
+    pub hit: Hit,
+    /// Форма, отбрасывающая тень от источников света.
+    pub occluder: Option<Occluder>,
+    pub gestures: GestureMode,
+    pub drag: Option<Drag>,
+    pub drop_target: bool,
+    pub anchor: Option<Anchor>,
     pub offset: Vec2,
     pub visible: bool,
+    pub cull: bool,
+    pub bounds: Option<(Vec2, Vec2)>,
+    /// Поддерево рисуется в эту цель, а не на экран.
+    pub viewport: Option<RenderTarget>,
+    pub material: Option<Material>,
+    pub blend: Blend,
+    /// Обводка фигур, кроме текстурных.
+    pub stroke: Option<Stroke>,
+    /// Заливка градиентом вместо сплошного цвета.
+    pub gradient: Option<Gradient>,
+    /// Число отрезков на полный круг вместо подбора по размеру на экране.
+    pub segments: Option<usize>,
     pub node: Vec<Node2d>,
     pub script: Vec<Box<dyn Module>>,
     pub hash: HashMap<&'static str, Box<dyn Any + Send + Sync>>,
+    id: u64,
     touch_id: Option<u64>,
+    parent_size: Vec2,
+    parent: Affine2,
+    transform: Affine2,
     render_id: usize,
+    target: Option<usize>,
     draw_update: DrawUpdate,
     chache: Chache,
     hidden: Hidden,
 }
 impl Node2d {
+    /// Уникальный номер узла, не меняется при перестройке дерева.
+    #[inline(always)]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
     pub fn get_node(&mut self, name: &str) -> Option<&mut Node2d> {
         let name = name.to_string();
         for obj in &mut self.node {
@@ -102,7 +392,9 @@ impl Node2d {
         }
 
         if let Some(id) = del_id {
-            Ok(self.node.remove(id))
+            let mut node = self.node.remove(id);
+            node.del_render();
+            Ok(node)
         } else {
             Err(format!("Not found object ({})", name))
         }
@@ -111,9 +403,15 @@ impl Node2d {
     pub fn add_node(&mut self, node: Vec<CreateNode2d>) {
         let node: Vec<Node2d> = node.into_iter().map(|n| {
             let mut n = n.get_node();
-            if n.visible {
+            if n.obj != Obj2d::None {
                 n.render_id = new_render();
             }
+            // Источники рисуют в буфер последнего добавленного узла освещения
+            if let Obj2d::Lighting(target) = &n.obj {
+                unsafe {
+                    LIGHTING = Some(target.id);
+                }
+            }
             n
         }).collect();
 
@@ -121,6 +419,63 @@ impl Node2d {
         
     }
 
+    pub fn spawn(&mut self, prefab: &Prefab, name: &str) -> &mut Node2d {
+        self.add_node(vec![prefab.instance(name)]);
+        self.node.last_mut().unwrap()
+    }
+
+    fn del_render(&mut self) {
+        if self.render_id != NO_RENDER {
+            del_render(self.render_id);
+        }
+
+        // Без узла буфер света больше не показывается, источники перестают в него рисовать
+        if let Obj2d::Lighting(target) = &self.obj {
+            unsafe {
+                if LIGHTING == Some(target.id) {
+                    LIGHTING = None;
+                }
+            }
+        }
+
+        for obj in &mut self.node {
+            obj.del_render();
+        }
+    }
+
+    /// Вершины меша для правки на месте, узел перерисуется в этом кадре.
+    pub fn mesh(&mut self) -> &mut Vec<(Vec2, Rgba, Vec2)> {
+        self.chache.dirty = true;
+
+        if let Obj2d::Mesh { vertices, .. } = &mut self.obj {
+            vertices
+        } else {
+            panic!("Not a Mesh object!")
+        }
+    }
+
+    /// Заменяет вершины и треугольники меша.
+    pub fn set_mesh(&mut self, new_vertices: Vec<(Vec2, Rgba, Vec2)>, new_indices: Vec<u16>) {
+        self.chache.dirty = true;
+
+        if let Obj2d::Mesh { vertices, indices, .. } = &mut self.obj {
+            *vertices = new_vertices;
+            *indices = new_indices;
+        } else {
+            panic!("Not a Mesh object!")
+        }
+    }
+
+    /// Точка мира в локальные координаты узла (с учётом всех родителей).
+    pub fn to_local(&self, world: Vec2) -> Vec2 {
+        self.transform.inverse().transform_point2(world)
+    }
+
+    /// Локальная точка узла в координаты мира.
+    pub fn to_global(&self, local: Vec2) -> Vec2 {
+        self.transform.transform_point2(local)
+    }
+
     pub fn set_hash<T: 'static + Send + Sync>(&mut self, key: &'static str, value: T) {
         self.hash.insert(key, Box::new(value));
     }
@@ -151,6 +506,19 @@ impl Node2d {
     pub(crate) fn update(&mut self) {
         self.upd_pos();
 
+        if let Obj2d::Sprite(sprite) = &mut self.obj {
+            if let Some(name) = sprite.advance(unsafe { DELTA }) {
+                self.scripts(|s, obj| s.animation_finished(obj, &name));
+            }
+        }
+
+        if let Obj2d::Particles(particles) = &mut self.obj {
+            let emitter = self.transform * Affine2::from_translation(self.chache.offset);
+            if particles.advance(unsafe { DELTA }, &emitter) {
+                self.chache.dirty = true;
+            }
+        }
+
         let mut scripts = take(&mut self.script);
 
         for s in &mut scripts {
@@ -161,22 +529,72 @@ impl Node2d {
 
         self.upd_pos();
 
-        if self.obj != Obj2d::None && (self.obj != self.hidden.obj
+        if let Obj2d::TileMap(map) = &mut self.obj {
+            map.advance(unsafe { DELTA });
+
+            // Кадр камеры в координатах карты
+            let local = (self.transform * Affine2::from_translation(self.chache.offset)).inverse();
+            let (min, max) = view_rect(self.target);
+            let (min, max) = aabb(&local, min, max);
+            map.cull(min, max);
+
+            if take(&mut map.dirty) {
+                self.chache.dirty = true;
+            }
+        }
+
+        // Буфер света следует за камерой
+        if let Obj2d::Lighting(target) = &self.obj {
+            let (min, max) = view_rect(self.target);
+            if target.cover(min, max) {
+                self.draw_update = DrawUpdate::Update;
+            }
+        }
+
+        if self.visible {
+            if let Some(occluder) = &self.occluder {
+                let world = self.transform * Affine2::from_translation(self.chache.offset);
+                let points = match occluder {
+                    Occluder::Rect(w, h) => {
+                        let (w, h) = (w / 2., h / 2.);
+                        vec![vec2(-w, -h), vec2(w, -h), vec2(w, h), vec2(-w, h)]
+                    }
+                    Occluder::Circle(r) => shape::ellipse(Vec2::splat(*r), 16),
+                    Occluder::Polygon(points) => points.clone(),
+                };
+
+                unsafe {
+                    OCCLUDERS.push(points.into_iter().map(|p| world.transform_point2(p)).collect());
+                }
+            }
+        }
+
+        let detail = self.detail();
+
+        // Меш, частицы и карта проверяются по флагу, без обхода вершин
+        let live = self.obj.live();
+        let changed = match live {
+            Some(_) => take(&mut self.chache.dirty) | (live != self.hidden.live),
+            None => self.obj != self.hidden.obj,
+        };
+
+        if self.obj != Obj2d::None && (changed
             || self.offset != self.hidden.offset
-            || self.scale != self.hidden.scale)
+            || self.stroke != self.hidden.stroke
+            || self.gradient != self.hidden.gradient
+            || detail != self.chache.detail)
         {
-            self.hidden.obj = self.obj.clone();
+            self.chache.detail = detail;
+            self.hidden.obj = match live {
+                Some(_) => Obj2d::None,
+                None => self.obj.clone(),
+            };
+            self.hidden.live = live;
             self.hidden.offset = self.offset;
-            self.hidden.scale = self.scale;
+            self.hidden.stroke = self.stroke;
+            self.hidden.gradient = self.gradient.clone();
 
-            self.chache.offset = match &self.obj {
-                Obj2d::Rect(w, h, _) => self.offset * vec2(*w, *h) * self.scale,
-                Obj2d::Circle(r) => self.offset * r * self.scale,
-                Obj2d::Texture(t) | Obj2d::Text(_, _, _, t) => {
-                    self.offset * vec2(t.width, t.height) * self.scale
-                }
-                Obj2d::None => vec2(0., 0.),
-            };
+            self.chache.offset = self.offset * self.obj.size() / 2.;
 
             if self.visible {
                 self.draw_update = DrawUpdate::Update;
@@ -184,17 +602,27 @@ impl Node2d {
             }
         } 
 
-        let parrent_pos = self.global_position + self.chache.offset / 2.;
+        // Дети крепятся к центру объекта с учётом offset,
+        // поддерево вьюпорта живёт в координатах своей цели
+        let (parent, target) = match self.viewport {
+            Some(viewport) => (Affine2::IDENTITY, Some(viewport.id)),
+            None => (self.transform * Affine2::from_translation(self.chache.offset), self.target),
+        };
 
-        if self.visible {
-            if self.rotation != self.hidden.rotation {
-                self.hidden.rotation = self.rotation;
-                self.draw_update = DrawUpdate::Update;
+        let size = match (self.viewport, &self.obj) {
+            (Some(viewport), _) => {
+                let t = viewport.texture();
+                vec2(t.width, t.height)
             }
-        }
+            (None, Obj2d::None) => self.chache.size,
+            (None, obj) => obj.size(),
+        };
 
         for obj in &mut self.node {
-            obj.parent_position = parrent_pos;
+            obj.target = target;
+            obj.parent = parent;
+            obj.parent_position = parent.translation;
+            obj.parent_size = size;
             obj.update();
         }
     }
@@ -203,9 +631,11 @@ impl Node2d {
         if self.visible != self.hidden.visible && self.obj != Obj2d::None {
             self.hidden.visible = self.visible;
             if self.visible {
+                self.alloc_render();
                 unsafe {
-                    RENDERS[self.render_id] = Some((vec![], vec![], None, DrawUpdate::Create));
+                    RENDERS[self.render_id] = Some(Render::new());
                 }
+                self.hidden.culled = false;
                 self.upd_img();
             } else {
                 unsafe {
@@ -214,58 +644,319 @@ impl Node2d {
             }
         }
 
-        if self.visible && self.chache.visible {
-            {
-                let mut color = self.color.get();
-                color[3] *= a;
+        if self.visible {
+            let mut color = self.color.get();
+            color[3] *= a;
+
+            if self.obj != Obj2d::None {
+                let culled = !self.chache.visible;
 
-                if self.draw_update != DrawUpdate::None {
-                    draw(
-                        self.render_id,
-                        self.global_position,
-                        &self.obj,
-                        self.scale,
-                        self.rotation,
-                        self.offset,
-                        color,
-                    );
+                if culled != self.hidden.culled {
+                    self.hidden.culled = culled;
+
+                    if culled {
+                        clear_render(self.render_id);
+                    } else {
+                        self.draw_update = DrawUpdate::Update;
+                    }
                 }
 
-                unsafe {
-                    if RENDERS[self.render_id].as_mut().unwrap().3 != DrawUpdate::Create {
-                        RENDERS[self.render_id].as_mut().unwrap().3 = self.draw_update;
+                if !culled {
+                    // Тени собираются за весь update, поэтому свет проверяет их здесь
+                    if let Obj2d::Light(light) = &self.obj {
+                        if light.shadow != Shadow::None && unsafe { SHADOWS_UPDATE } {
+                            self.draw_update = DrawUpdate::Update;
+                        }
+
+                        // Буфер света удалили или создали новый
+                        let target = unsafe { RENDERS[self.render_id].as_ref() }.and_then(|r| r.target);
+                        if target != unsafe { LIGHTING } {
+                            self.draw_update = DrawUpdate::Update;
+                            self.upd_img();
+                        }
+                    }
+
+                    // Доп. текстуры материала читаются по uv основной, поэтому
+                    // на время отрисовки основная подменяется копией не из атласа
+                    let images = self.material.as_ref().is_some_and(|m| !m.images.is_empty());
+                    let drawn = unsafe { RENDERS[self.render_id].as_ref() }
+                        .is_some_and(|r| r.material.as_ref().is_some_and(|m| !m.images.is_empty()));
+
+                    if images != drawn {
+                        self.draw_update = DrawUpdate::Update;
+                    }
+
+                    let packed = match self.obj.main_texture() {
+                        Some(texture) if images => {
+                            let packed = *texture;
+                            *texture = packed.unpacked();
+                            Some(packed)
+                        }
+                        _ => None,
+                    };
+
+                    if self.draw_update != DrawUpdate::None {
+                        draw(
+                            self.render_id,
+                            &self.transform,
+                            &self.obj,
+                            self.chache.offset,
+                            &Paint {
+                                color,
+                                gradient: self.gradient.as_ref(),
+                            },
+                            self.stroke.map(|mut s| {
+                                s.color.a *= a;
+                                s
+                            }),
+                            self.chache.detail,
+                        );
+                    }
+
+                    unsafe {
+                        let render = RENDERS[self.render_id].as_mut().unwrap();
+                        if render.update != DrawUpdate::Create {
+                            render.update = self.draw_update;
+                        }
+                        // Значения материала меняются скриптами без перестройки геометрии
+                        if render.material != self.material {
+                            render.material = self.material.clone();
+                        }
+                        render.blend = self.blend;
+
+                        if images != drawn || packed.is_some() {
+                            render.texture = self.obj.texture_id();
+                        }
+                    }
+
+                    if let (Some(packed), Some(texture)) = (packed, self.obj.main_texture()) {
+                        *texture = packed;
                     }
+
+                    self.draw_update = DrawUpdate::None;
                 }
+            }
 
-                self.draw_update = DrawUpdate::None;
+            if self.chache.subtree {
+                self.hidden.subtree = true;
 
                 for obj in &mut self.node {
                     obj.draw(color[3]);
                 }
+            } else if self.hidden.subtree {
+                self.hidden.subtree = false;
+
+                for obj in &mut self.node {
+                    obj.cull();
+                }
+            }
+        }
+    }
+
+    /// Убирает поддерево из кадра, дальше его обход пропускается,
+    /// пока границы поддерева не вернутся в кадр.
+    fn cull(&mut self) {
+        if self.obj != Obj2d::None && !self.hidden.culled {
+            self.hidden.culled = true;
+            clear_render(self.render_id);
+        }
+
+        if self.hidden.subtree {
+            self.hidden.subtree = false;
+
+            for obj in &mut self.node {
+                obj.cull();
             }
         }
     }
 
     #[inline(always)]
     fn upd_pos(&mut self) {
-        let c = if self.global_position != self.hidden.global_position {
-            self.position = self.global_position - self.parent_position;
-            true
-        } else if self.position != self.hidden.position {
-            true
-        } else if self.parent_position != self.hidden.parrent_position {
-            true
-        } else if unsafe { CANVAS_UPDATE } {
-            true
+        self.upd_layout();
+
+        let parent = self.parent_transform();
+
+        let mut c = parent != self.hidden.parent;
+
+        if self.global_position != self.hidden.global_position {
+            self.position = parent.inverse().transform_point2(self.global_position);
+            c = true;
+        }
+
+        if self.global_rotation != self.hidden.global_rotation {
+            let (_, rotation, _) = parent.to_scale_angle_translation();
+            self.rotation = self.global_rotation - rotation;
+            c = true;
+        }
+
+        if self.global_scale != self.hidden.global_scale {
+            let (scale, _, _) = parent.to_scale_angle_translation();
+            self.scale = self.global_scale / scale;
+            c = true;
+        }
+
+        if c
+            || self.position != self.hidden.position
+            || self.rotation != self.hidden.rotation
+            || self.scale != self.hidden.scale
+            || self.pivot != self.hidden.pivot
+            || unsafe { CANVAS_UPDATE }
+        {
+            self.draw_update = DrawUpdate::Update;
+
+            // parent * T(position) * R(rotation) * S(scale) * T(-pivot)
+            self.transform = parent
+                * Affine2::from_scale_angle_translation(self.scale, self.rotation, self.position)
+                * Affine2::from_translation(-self.pivot);
+
+            let (scale, rotation, _) = self.transform.to_scale_angle_translation();
+
+            self.global_position = parent.transform_point2(self.position);
+            self.global_rotation = rotation;
+            self.global_scale = scale;
+
+            self.hidden.parent = parent;
+            self.hidden.global_position = self.global_position;
+            self.hidden.global_rotation = self.global_rotation;
+            self.hidden.global_scale = self.global_scale;
+            self.hidden.position = self.position;
+            self.hidden.rotation = self.rotation;
+            self.hidden.scale = self.scale;
+            self.hidden.pivot = self.pivot;
+
+            if self.visible {
+                self.upd_vsbl();
+            }
+        }
+    }
+
+    /// Раскладка по якорям: размер и позиция от прямоугольника родителя,
+    /// а без размера у родителя (или при `keep`) от видимого холста.
+    #[inline(always)]
+    fn upd_layout(&mut self) {
+        let anchor = match self.anchor {
+            Some(anchor) => anchor,
+            None => return,
+        };
+
+        if !unsafe { CANVAS_UPDATE }
+            && self.hidden.anchor == self.anchor
+            && self.hidden.parent_size == self.parent_size
+        {
+            return;
+        }
+
+        self.hidden.anchor = self.anchor;
+        self.hidden.parent_size = self.parent_size;
+
+        let (min, max) = if matches!(self.keep, Keep::Canvas) && self.parent_size != Vec2::ZERO {
+            (-self.parent_size / 2., self.parent_size / 2.)
         } else {
-            false
+            let parent = self.parent_transform().inverse();
+            let a = parent.transform_point2(unsafe { CAMERA2D - CANVAS_PROJ });
+            let b = parent.transform_point2(unsafe { CAMERA2D + CANVAS_PROJ });
+            (a.min(b), a.max(b))
         };
 
-        if c {
-            self.draw_update = DrawUpdate::Update;
+        let area = max - min;
+        let margin_min = vec2(anchor.margin[0], anchor.margin[1]);
+        let margin_max = vec2(anchor.margin[2], anchor.margin[3]);
+
+        let lo = min + anchor.min * area + margin_min;
+        let hi = min + anchor.max * area - margin_max;
+
+        let stretch = match anchor.stretch {
+            Stretch::None => (false, false),
+            Stretch::Width => (true, false),
+            Stretch::Height => (false, true),
+            Stretch::Both => (true, true),
+        };
+
+        let mut size = match self.obj {
+            Obj2d::None => self.chache.size,
+            _ => self.obj.size(),
+        } * self.scale;
+
+        // Без растягивания узел прижимается к точке якоря своим краем
+        let point = min + anchor.min * area;
+        let side = |p: f32, size: f32, a: f32, before: f32, after: f32| {
+            let from = before + size / 2.;
+            let to = -(after + size / 2.);
+            p + from + (to - from) * a
+        };
+
+        let mut center = Vec2::ZERO;
+
+        if stretch.0 {
+            size.x = (hi.x - lo.x).max(0.);
+            center.x = (lo.x + hi.x) / 2.;
+        } else {
+            center.x = side(point.x, size.x, anchor.min.x, margin_min.x, margin_max.x);
+        }
+
+        if stretch.1 {
+            size.y = (hi.y - lo.y).max(0.);
+            center.y = (lo.y + hi.y) / 2.;
+        } else {
+            center.y = side(point.y, size.y, anchor.min.y, margin_min.y, margin_max.y);
+        }
+
+        if stretch.0 || stretch.1 {
+            let local = size / self.scale;
+
+            match &mut self.obj {
+                Obj2d::Rect(w, h, _) | Obj2d::NineSlice(NineSlice { width: w, height: h, .. }) => {
+                    *w = local.x;
+                    *h = local.y;
+                }
+                Obj2d::Circle(r) | Obj2d::Pie(r, _, _) | Obj2d::Arc(r, _, _, _) => {
+                    *r = local.min_element() / 2.
+                }
+                Obj2d::Ellipse(rx, ry) => {
+                    *rx = local.x / 2.;
+                    *ry = local.y / 2.;
+                }
+                Obj2d::Ring(r, inner) => {
+                    let k = local.min_element() / 2. / r.max(f32::EPSILON);
+                    *r *= k;
+                    *inner *= k;
+                }
+                Obj2d::Texture(t)
+                | Obj2d::Text(_, _, _, t)
+                | Obj2d::Sprite(Sprite { texture: t, .. }) => {
+                    self.scale = size / vec2(t.width, t.height);
+                }
+                Obj2d::Polygon(_) | Obj2d::Polyline(_, _) | Obj2d::Mesh { .. } => {
+                    self.scale = size / self.obj.size().max(Vec2::splat(f32::EPSILON));
+                }
+                Obj2d::Particles(_) | Obj2d::TileMap(_) | Obj2d::Light(_) | Obj2d::Lighting(_) => {}
+                Obj2d::None => self.chache.size = local,
+            }
+        }
 
-            self.global_position = unsafe { match self.keep {
-                Keep::Canvas => self.parent_position,
+        self.position = center - self.offset * size / 2. + self.pivot * self.scale;
+    }
+
+    /// Отрезков на круг: чем крупнее фигура на экране, тем больше.
+    #[inline(always)]
+    fn detail(&self) -> usize {
+        let r = self.obj.radius();
+        if r <= 0. {
+            return 0;
+        }
+
+        self.segments.unwrap_or_else(|| {
+            let m = self.transform.matrix2;
+            let scale = m.x_axis.length().max(m.y_axis.length());
+            shape::segments(r * scale * pixel_scale(self.target))
+        })
+    }
+
+    /// Трансформ родителя, с учётом привязки `keep` к камере.
+    #[inline(always)]
+    fn parent_transform(&self) -> Affine2 {
+        let anchor = unsafe { match self.keep {
+                Keep::Canvas => return self.parent,
                 Keep::Center => CAMERA2D,
                 Keep::Up => CAMERA2D + vec2(0., -CANVAS_PROJ.y),
                 Keep::Down => CAMERA2D + vec2(0., CANVAS_PROJ.y),
@@ -275,48 +966,74 @@ impl Node2d {
                 Keep::LeftDown => CAMERA2D + vec2(-CANVAS_PROJ.x, CANVAS_PROJ.y),
                 Keep::RightUp => CAMERA2D + vec2(CANVAS_PROJ.x, -CANVAS_PROJ.y),
                 Keep::RightDown => CAMERA2D + CANVAS_PROJ,
-            } } + self.position;
-        
-            self.hidden.parrent_position = self.parent_position;
-            self.hidden.global_position = self.global_position;
-            self.hidden.position = self.position;
+        } };
 
-            if self.visible {
-                self.upd_vsbl();
-            }
+        Affine2::from_translation(anchor)
+    }
+
+    /// Узлу без объекта слот выделяется, только когда объект появится,
+    /// поэтому такой узел рисуется поверх добавленных раньше.
+    #[inline(always)]
+    fn alloc_render(&mut self) {
+        if self.render_id == NO_RENDER {
+            self.render_id = new_render();
         }
     }
 
     #[inline(always)]
     fn upd_img(&mut self) {
-        let c = match &self.obj {
-            Obj2d::Rect(_, _, _) => None,
-            Obj2d::Circle(_) => None,
-            Obj2d::Texture(t) | Obj2d::Text(_, _, _, t) => Some(t.id),
-            Obj2d::None => None,
-        };
+        self.alloc_render();
+        let c = self.obj.texture_id();
 
         unsafe {
-            if c != RENDERS[self.render_id].as_mut().unwrap().2 {
-                RENDERS[self.render_id].as_mut().unwrap().2 = c;
+            let render = RENDERS[self.render_id].as_mut().unwrap();
+            if c != render.texture {
+                render.texture = c;
             }
+            // Свет рисуется в буфер света, а не туда же, куда узел
+            render.target = match self.obj {
+                Obj2d::Light(_) => LIGHTING,
+                _ => self.target,
+            };
         }
 
         self.upd_vsbl();
     }
 
+    /// Отсечение по AABB объекта в мире против видимой области камеры.
     #[inline(always)]
     fn upd_vsbl(&mut self) {
-        let obj_povi = match &self.obj {
-            Obj2d::Rect(w, h, _) => (w.powi(2) + h.powi(2)).sqrt(),
-            Obj2d::Circle(r) => (r.powi(2) + r.powi(2)).sqrt(),
-            Obj2d::Texture(t) | Obj2d::Text(_, _, _, t) => (t.width.powi(2) + t.height.powi(2)).sqrt(),
-            Obj2d::None => 0.,
-        } / 2.;
+        let view = view_rect(self.target);
+
+        let (min, max) = match &self.obj {
+            Obj2d::None => (self.transform.translation, self.transform.translation),
+            Obj2d::Circle(r) => {
+                // Эллипс после трансформа: полуоси по x и y из столбцов матрицы
+                let center = self.transform.transform_point2(self.chache.offset);
+                let m = self.transform.matrix2;
+                let extent = vec2(m.x_axis.x.hypot(m.y_axis.x), m.x_axis.y.hypot(m.y_axis.y)) * *r;
+                (center - extent, center + extent)
+            }
+            Obj2d::Particles(p) => match p.space {
+                ParticleSpace::Local => aabb(
+                    &self.transform,
+                    p.bounds.0 + self.chache.offset,
+                    p.bounds.1 + self.chache.offset,
+                ),
+                ParticleSpace::World => p.bounds,
+            },
+            obj => {
+                let half = obj.size() / 2.;
+                aabb(&self.transform, self.chache.offset - half, self.chache.offset + half)
+            }
+        };
 
-        let d = (self.position - unsafe { CAMERA2D }).abs() - obj_povi;
+        self.chache.visible = !self.cull || overlap((min, max), view);
 
-        self.chache.visible = d.x < unsafe { CANVAS_PROJ.x } && d.y < unsafe { CANVAS_PROJ.y };
+        self.chache.subtree = match self.bounds {
+            Some((min, max)) => !self.cull || overlap(aabb(&self.transform, min, max), view),
+            None => true,
+        };
     }
 
     /*pub(crate) fn key(&mut self, key: &Key, keymod: KeyMods, touch: &Touch) {
@@ -329,122 +1046,570 @@ impl Node2d {
         }
     }*/
 
-    pub(crate) fn touch(&mut self, id: u64, touch: &Touch, pos: Vec2) {
-        for obj in &mut self.node.iter_mut().rev() {
-            if unsafe { ON_TOUCH } {
-                obj.touch(id, touch, pos);
-            } else {
-                break;
+    /// Попадание точки мира в объект через обратный трансформ узла.
+    #[inline(always)]
+    fn hit(&self, pos: Vec2) -> bool {
+        let local = self.to_local(pos) - self.chache.offset;
+
+        let bounds = || match &self.obj {
+            Obj2d::Circle(r) => local.length() < *r,
+            Obj2d::Ellipse(rx, ry) => (local / vec2(*rx, *ry)).length() < 1.,
+            Obj2d::Arc(r, start, end, line) => {
+                (local.length() - r).abs() < line.width / 2. && shape::in_sweep(local, *start, *end)
             }
-        }
-
+            Obj2d::Pie(r, start, end) => local.length() < *r && shape::in_sweep(local, *start, *end),
+            Obj2d::Ring(r, inner) => (*inner..*r).contains(&local.length()),
+            Obj2d::Polygon(points) => inside(local, points),
+            Obj2d::None => true,
+            obj => {
+                let half = obj.size() / 2.;
+                local.x.abs() < half.x && local.y.abs() < half.y
+            }
+        };
+
+        match &self.hit {
+            Hit::Bounds => bounds(),
+            Hit::Alpha(threshold) => match &self.obj {
+                Obj2d::Texture(t)
+                | Obj2d::Text(_, _, _, t)
+                | Obj2d::Sprite(Sprite { texture: t, .. }) => {
+                    let uv = local / vec2(t.width, t.height) + 0.5;
+                    texture_alpha(t, uv) as f32 / 255. >= *threshold
+                }
+                _ => bounds(),
+            },
+            Hit::Circle(r) => local.length() < *r,
+            Hit::Rect(w, h) => local.x.abs() < w / 2. && local.y.abs() < h / 2.,
+            Hit::Polygon(points) => inside(local, points),
+        }
+    }
+
+    pub(crate) fn touch(&mut self, id: u64, touch: &Touch, pos: Vec2) {
+        match touch {
+            Touch::Press => self.drag_press(id, pos),
+            Touch::Move => self.drag_move(id, pos),
+            Touch::Relese => self.drag_release(id, pos),
+        }
+
+        let mut targets = Vec::new();
+
+        // Цели сверху вниз: последние дети рисуются поверх, поэтому первыми
+        match touch {
+            Touch::Press => self.pick(pos, &mut Vec::new(), &mut targets, Node2d::input_target),
+            Touch::Move | Touch::Relese => self.captured(id, &mut Vec::new(), &mut targets),
+        }
+
+        for path in &targets {
+            if !unsafe { ON_TOUCH } {
+                break;
+            }
+
+            self.dispatch(path, id, touch, pos);
+        }
+
+        match touch {
+            Touch::Press | Touch::Move => self.hover(id, touch, pos),
+            // Палец убран с экрана: указатель покидает все узлы
+            Touch::Relese if DEVICE == 1 => self.unhover(id, pos),
+            _ => {}
+        }
+    }
+
+    pub(crate) fn gesture(&mut self, gesture: &Gesture) {
+        for obj in self.node.iter_mut().rev() {
+            obj.gesture(gesture);
+        }
+
+        if match self.gestures {
+            GestureMode::Off => false,
+            GestureMode::Global => true,
+            GestureMode::Hit => self.hit(gesture.pos()),
+        } {
+            self.scripts(|s, obj| s.gesture(obj, gesture));
+        }
+    }
+
+    fn drag_press(&mut self, id: u64, pos: Vec2) {
+        if unsafe { DRAG.is_some() } {
+            return;
+        }
+
+        let mut path = Vec::new();
+        if !self.pick_top(pos, &mut path, |n| n.drag.is_some() && n.visible) {
+            return;
+        }
+
+        let node = self.at(&path).unwrap();
+        let drag = node.drag.clone().unwrap();
+
+        let ghost = drag.ghost.then(|| {
+            let mut color = node.color;
+            color.a *= 0.5;
+
+            let mut ghost = CreateNode2d::new(GHOST, node.obj.clone())
+                .rotation(node.global_rotation)
+                .scale(node.global_scale.x, node.global_scale.y)
+                .offset(node.offset.x, node.offset.y)
+                .color(color);
+            ghost.node2d.global_position = node.global_position;
+            ghost.node2d.pivot = node.pivot;
+            ghost.node2d.material = node.material.clone();
+            ghost.node2d.blend = node.blend;
+            ghost.node2d.stroke = node.stroke;
+            ghost.node2d.gradient = node.gradient.clone();
+            ghost.node2d.segments = node.segments;
+            ghost
+        });
+
+        let dragging = Dragging {
+            id,
+            node: node.id,
+            start: node.position,
+            grab: node.global_position - pos,
+            ghost: ghost.as_ref().map(|g| g.node2d.id),
+            payload: drag.payload,
+        };
+
+        node.scripts(|s, obj| s.drag_start(obj, pos));
+
+        if let Some(ghost) = ghost {
+            self.add_node(vec![ghost]);
+        }
+
+        unsafe {
+            DRAG = Some(dragging);
+        }
+    }
+
+    fn drag_move(&mut self, id: u64, pos: Vec2) {
+        let drag = match unsafe { DRAG.as_ref() } {
+            Some(drag) if drag.id == id => drag,
+            _ => return,
+        };
+
+        let node = match self.find(drag.node) {
+            Some(node) => node,
+            None => return,
+        };
+
+        let parent = node.parent_transform();
+        let mut local = parent.inverse().transform_point2(pos + drag.grab);
+
+        if let Some(config) = &node.drag {
+            match config.axis {
+                Axis::X => local.y = drag.start.y,
+                Axis::Y => local.x = drag.start.x,
+                Axis::Both => {}
+            }
+
+            if let Some((min, max)) = config.bounds {
+                local = local.clamp(min, max);
+            }
+        }
+
+        if let Some(ghost) = drag.ghost {
+            let world = parent.transform_point2(local);
+
+            if let Some(ghost) = self.node.iter_mut().find(|n| n.id == ghost) {
+                ghost.global_position = world;
+            }
+        } else {
+            node.position = local;
+        }
+
+        if let Some(target) = self.drop_at(pos, drag.node) {
+            target.scripts(|s, obj| s.drag_over(obj, drag.payload.as_deref(), pos));
+        }
+    }
+
+    fn drag_release(&mut self, id: u64, pos: Vec2) {
+        let drag = match unsafe { DRAG.take_if(|drag| drag.id == id) } {
+            Some(drag) => drag,
+            None => return,
+        };
+
+        let mut accepted = false;
+
+        if let Some(target) = self.drop_at(pos, drag.node) {
+            target.scripts(|s, obj| accepted |= s.drop(obj, drag.payload.as_deref(), pos));
+        }
+
+        let mut dropped = None;
+
+        if let Some(ghost) = drag.ghost {
+            if let Some(i) = self.node.iter().position(|n| n.id == ghost) {
+                let mut ghost = self.node.remove(i);
+                ghost.del_render();
+                dropped = Some(ghost.global_position);
+            }
+        }
+
+        if let Some(node) = self.find(drag.node) {
+            if accepted {
+                if let Some(world) = dropped {
+                    node.global_position = world;
+                }
+            } else if node.drag.as_ref().is_some_and(|d| d.snap_back) {
+                node.position = drag.start;
+            }
+
+            node.scripts(|s, obj| s.drag_end(obj, accepted));
+        }
+    }
+
+    /// Верхняя цель сброса под точкой, кроме самого перетаскиваемого поддерева.
+    fn drop_at(&mut self, pos: Vec2, dragged: u64) -> Option<&mut Node2d> {
+        let mut found = Vec::new();
+        self.pick(pos, &mut Vec::new(), &mut found, |n| n.drop_target && n.visible);
+
+        let path = found.into_iter().find(|path| {
+            let mut node: &Node2d = self;
+
+            node.id != dragged
+                && path.iter().all(|&i| {
+                    node = &node.node[i];
+                    node.id != dragged
+                })
+        })?;
+        self.at(&path)
+    }
+
+    /// Принимает ли узел ввод: `Auto` только со скриптами.
+    #[inline(always)]
+    fn input_target(&self) -> bool {
+        match self.input {
+            Input::Auto => !self.script.is_empty(),
+            Input::Block => true,
+            Input::Pass => false,
+        }
+    }
+
+    fn pick(&self, pos: Vec2, path: &mut Vec<usize>, out: &mut Vec<Vec<usize>>, filter: fn(&Node2d) -> bool) {
+        // Поддерево вьюпорта не в координатах мира, указатель его не задевает
+        let children = if self.viewport.is_some() { &[][..] } else { &self.node[..] };
+
+        for (i, obj) in children.iter().enumerate().rev() {
+            path.push(i);
+            obj.pick(pos, path, out, filter);
+            path.pop();
+        }
+
+        if filter(self) && self.hit(pos) {
+            out.push(path.clone());
+        }
+    }
+
+    /// Верхний узел под точкой, `path` - путь к нему от корня.
+    fn pick_top(&self, pos: Vec2, path: &mut Vec<usize>, filter: fn(&Node2d) -> bool) -> bool {
+        let children = if self.viewport.is_some() { &[][..] } else { &self.node[..] };
+
+        for (i, obj) in children.iter().enumerate().rev() {
+            path.push(i);
+            if obj.pick_top(pos, path, filter) {
+                return true;
+            }
+            path.pop();
+        }
+
+        filter(self) && self.hit(pos)
+    }
+
+    fn captured(&self, id: u64, path: &mut Vec<usize>, out: &mut Vec<Vec<usize>>) {
+        for (i, obj) in self.node.iter().enumerate().rev() {
+            path.push(i);
+            obj.captured(id, path, out);
+            path.pop();
+        }
+
+        if self.touch_id == Some(id) {
+            out.push(path.clone());
+        }
+    }
+
+    fn at(&mut self, path: &[usize]) -> Option<&mut Node2d> {
+        let mut node = self;
+        for &i in path {
+            node = node.node.get_mut(i)?;
+        }
+        Some(node)
+    }
+
+    #[inline(always)]
+    fn scripts(&mut self, mut f: impl FnMut(&dyn Module, &mut Node2d)) {
+        let scripts = take(&mut self.script);
+
+        for s in &scripts {
+            f(s.as_ref(), self);
+        }
+
+        self.script = scripts;
+    }
+
+    /// Погружение от корня к родителю цели, сама цель, затем всплытие обратно.
+    fn dispatch(&mut self, path: &[usize], id: u64, touch: &Touch, pos: Vec2) {
+        unsafe {
+            PROPAGATION = true;
+        }
+
+        for depth in 0..path.len() {
+            if let Some(node) = self.at(&path[..depth]) {
+                node.scripts(|s, obj| s.capture(obj, id, touch, pos));
+            }
+
+            if !unsafe { PROPAGATION } {
+                unsafe {
+                    ON_TOUCH = false;
+                }
+                return;
+            }
+        }
+
+        let node = match self.at(path) {
+            Some(node) => node,
+            None => return,
+        };
+
+        match touch {
+            Touch::Press => node.touch_id = Some(id),
+            Touch::Relese => node.touch_id = None,
+            _ => {}
+        }
+
+        // Скрипт по умолчанию возвращает ON_TOUCH, и поиск цели идёт дальше
+        unsafe {
+            ON_TOUCH = false;
+        }
+
+        node.scripts(|s, obj| s.touch(obj, id, touch, pos));
+
         if unsafe { ON_TOUCH } {
-            if self.script.len() != 0 {
-                if match touch {
-                    Touch::Press => {
-                        let dx = pos.x - self.global_position.x;
-                        let dy = pos.y - self.global_position.y;
-
-                        let sin = self.rotation.sin();
-                        let cos = self.rotation.cos();
-
-                        let local_x = cos * dx + sin * dy;
-                        let local_y = -sin * dx + cos * dy;
-
-                        if match &self.obj {
-                            Obj2d::Rect(w, h, _) => {
-                                ((local_x - self.chache.offset.x).abs()) / self.scale.x
-                                    + self.chache.offset.x
-                                    < w / 2.
-                                    && ((local_y - self.chache.offset.y).abs()) / self.scale.y
-                                        + self.chache.offset.y
-                                        < h / 2.
-                            }
-                            Obj2d::Circle(r) => {
-                                (((local_x - self.chache.offset.x) / self.scale.x).powi(2)
-                                    + ((local_y - self.chache.offset.y) / self.scale.y).powi(2))
-                                .sqrt()
-                                    < *r
-                            }
-                            Obj2d::Texture(t) | Obj2d::Text(_, _, _, t) => {
-                                ((local_x - self.chache.offset.x).abs()) / self.scale.x
-                                    < t.width / 2.
-                                    && ((local_y - self.chache.offset.y).abs()) / self.scale.y
-                                        < t.height / 2.
-                            }
-                            Obj2d::None => true,
-                        } {
-                            self.touch_id = Some(id);
-                            true
-                        } else {
-                            false
-                        }
-                    }
-                    Touch::Relese => {
-                        if self.touch_id == Some(id) {
-                            self.touch_id = None;
-                            true
-                        } else {
-                            false
-                        }
-                    }
-                    Touch::Move => self.touch_id == Some(id),
-                } {
-                    unsafe {
-                        ON_TOUCH = false;
-                    }
+            return;
+        }
 
-                    let mut scripts = take(&mut self.script);
+        for depth in (0..path.len()).rev() {
+            if !unsafe { PROPAGATION } {
+                break;
+            }
 
-                    for s in &mut scripts {
-                        s.touch(self, id, touch, pos);
-                    }
+            if let Some(node) = self.at(&path[..depth]) {
+                node.scripts(|s, obj| s.bubble(obj, id, touch, pos));
+            }
+        }
+    }
+
+    /// Наведение: цель и её родители-приёмники ввода под указателем.
+    /// Цель ищется заново, только если указатель сдвинулся или дерево обновилось.
+    fn hover(&mut self, id: u64, touch: &Touch, pos: Vec2) {
+        let frame = unsafe { UPDATE_FRAME };
+        let hovering = unsafe {
+            match HOVER.iter().position(|h| h.id == id) {
+                Some(i) => &mut HOVER[i],
+                None => {
+                    HOVER.push(Hovering { id, pos: Vec2::NAN, frame, nodes: Vec::new() });
+                    HOVER.last_mut().unwrap()
+                }
+            }
+        };
+
+        if hovering.pos == pos && hovering.frame == frame {
+            return;
+        }
+        hovering.pos = pos;
+        hovering.frame = frame;
+
+        let mut path = take(unsafe { &mut PICK });
+        let mut nodes = take(unsafe { &mut PICK_NODES });
+        path.clear();
+        nodes.clear();
+
+        if self.pick_top(pos, &mut path, Node2d::input_target) {
+            let mut node: &Node2d = self;
+
+            for depth in 0..=path.len() {
+                if depth > 0 {
+                    node = &node.node[path[depth - 1]];
+                }
 
-                    self.script = scripts;
+                if node.input_target() {
+                    nodes.push(node.id);
                 }
             }
         }
+
+        let last = take(&mut hovering.nodes);
+
+        for &node in last.iter().filter(|n| !nodes.contains(n)) {
+            if let Some(node) = self.find(node) {
+                node.scripts(|s, obj| s.pointer(obj, id, &Pointer::Exit, pos));
+            }
+        }
+
+        for depth in 0..=path.len() {
+            let node = match self.at(&path[..depth]) {
+                Some(node) if nodes.contains(&node.id) => node,
+                Some(_) => continue,
+                None => break,
+            };
+
+            if !last.contains(&node.id) {
+                node.scripts(|s, obj| s.pointer(obj, id, &Pointer::Enter, pos));
+            }
+
+            if let Touch::Move = touch {
+                node.scripts(|s, obj| s.pointer(obj, id, &Pointer::Hover, pos));
+            }
+        }
+
+        unsafe {
+            if let Some(hovering) = HOVER.iter_mut().find(|h| h.id == id) {
+                hovering.nodes = nodes;
+            }
+
+            PICK = path;
+            PICK_NODES = last;
+        }
+    }
+
+    fn unhover(&mut self, id: u64, pos: Vec2) {
+        let hovering = match unsafe { HOVER.iter().position(|h| h.id == id) } {
+            Some(i) => unsafe { HOVER.swap_remove(i) },
+            None => return,
+        };
+
+        for node in hovering.nodes {
+            if let Some(node) = self.find(node) {
+                node.scripts(|s, obj| s.pointer(obj, id, &Pointer::Exit, pos));
+            }
+        }
+    }
+
+    fn find(&mut self, id: u64) -> Option<&mut Node2d> {
+        if self.id == id {
+            return Some(self);
+        }
+
+        self.node.iter_mut().find_map(|n| n.find(id))
     }
 }
 
+#[inline(always)]
+fn aabb(transform: &Affine2, min: Vec2, max: Vec2) -> (Vec2, Vec2) {
+    let corners = [
+        transform.transform_point2(min),
+        transform.transform_point2(vec2(max.x, min.y)),
+        transform.transform_point2(max),
+        transform.transform_point2(vec2(min.x, max.y)),
+    ];
+
+    corners[1..].iter().fold((corners[0], corners[0]), |(lo, hi), p| (lo.min(*p), hi.max(*p)))
+}
+
+/// Точка внутри многоугольника (правило чётности пересечений).
+pub(crate) fn inside(p: Vec2, points: &[Vec2]) -> bool {
+    let mut result = false;
+    let mut j = points.len().wrapping_sub(1);
+
+    for i in 0..points.len() {
+        let (a, b) = (points[i], points[j]);
+
+        if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            result = !result;
+        }
+        j = i;
+    }
+
+    result
+}
+
+#[inline(always)]
+fn overlap(a: (Vec2, Vec2), b: (Vec2, Vec2)) -> bool {
+    a.0.cmple(b.1).all() && a.1.cmpge(b.0).all()
+}
+
 pub struct CreateNode2d {
     pub node2d: Node2d,
 }
 
 impl CreateNode2d {
     pub fn new(name: &str, obj: Obj2d) -> Self {
+        // Меш, частицы и карта не сравниваются, копия им не нужна
+        let hidden = match obj.live() {
+            Some(_) => Obj2d::None,
+            None => obj.clone(),
+        };
+
         Self {
             node2d: Node2d {
                 name: name.to_string(),
-                obj: obj.clone(),
+                obj,
                 parent_position: Vec2::ZERO,
                 global_position: Vec2::ZERO,
                 position: Vec2::ZERO,
                 rotation: 0.,
                 scale: Vec2::new(1., 1.),
+                global_rotation: 0.,
+                global_scale: Vec2::new(1., 1.),
+                pivot: Vec2::ZERO,
                 color: rgb(234, 234, 234),
                 visible: true,
+                cull: true,
+                bounds: None,
+                viewport: None,
+                material: None,
+                blend: Blend::Alpha,
                 keep: Keep::Canvas,
+                input: Input::Auto,
+                hit: Hit::Bounds,
+                occluder: None,
+                gestures: GestureMode::Off,
+                drag: None,
+                drop_target: false,
+                anchor: None,
                 offset: Vec2::ZERO,
+                stroke: None,
+                gradient: None,
+                segments: None,
                 node: Vec::new(),
                 script: Vec::new(),
                 hash: HashMap::new(),
+                id: unsafe {
+                    NODE_ID += 1;
+                    NODE_ID
+                },
                 touch_id: None,
-                render_id: 0,
+                parent_size: Vec2::ZERO,
+                parent: Affine2::IDENTITY,
+                transform: Affine2::IDENTITY,
+                render_id: NO_RENDER,
+                target: None,
                 draw_update: DrawUpdate::Create,
                 chache: Chache {
                     offset: Vec2::ZERO,
+                    size: Vec2::ZERO,
+                    detail: 0,
+                    dirty: false,
                     visible: true,
+                    subtree: true,
                 },
                 hidden: Hidden {
-                    obj,
-                    parrent_position: Vec2::ZERO,
+                    obj: hidden,
+                    live: None,
+                    anchor: None,
+                    parent_size: Vec2::ZERO,
+                    parent: Affine2::IDENTITY,
                     global_position: Vec2::ZERO,
+                    global_rotation: 0.,
+                    global_scale: Vec2::new(1., 1.),
                     position: Vec2::ZERO,
                     rotation: 0.,
                     scale: Vec2::ZERO,
+                    pivot: Vec2::ZERO,
                     offset: Vec2::ZERO,
+                    stroke: None,
+                    gradient: None,
                     visible: false,
+                    culled: false,
+                    subtree: true,
                 },
             },
         }
@@ -465,6 +1630,11 @@ impl CreateNode2d {
         self
     }
 
+    pub fn name(mut self, name: &str) -> Self {
+        self.node2d.name = name.to_string();
+        self
+    }
+
     pub fn position(mut self, x: f32, y: f32) -> Self {
         self.node2d.position = vec2(x, y);
         self
@@ -480,6 +1650,11 @@ impl CreateNode2d {
         self
     }
 
+    pub fn pivot(mut self, x: f32, y: f32) -> Self {
+        self.node2d.pivot = vec2(x, y);
+        self
+    }
+
     pub fn color(mut self, color: Rgba) -> Self {
         self.node2d.color = color;
         self
@@ -490,16 +1665,101 @@ impl CreateNode2d {
         self
     }
 
+    pub fn cull(mut self, sel: bool) -> Self {
+        self.node2d.cull = sel;
+        self
+    }
+
+    /// Границы всего поддерева в локальных координатах узла:
+    /// если они вне кадра, дети не обходятся при отрисовке.
+    pub fn bounds(mut self, x1: f32, y1: f32, x2: f32, y2: f32) -> Self {
+        self.node2d.bounds = Some((vec2(x1, y1), vec2(x2, y2)));
+        self
+    }
+
+    pub fn material(mut self, material: &Material) -> Self {
+        self.node2d.material = Some(material.clone());
+        self
+    }
+
+    pub fn blend(mut self, blend: Blend) -> Self {
+        self.node2d.blend = blend;
+        self
+    }
+
+    pub fn stroke(mut self, stroke: Stroke) -> Self {
+        self.node2d.stroke = Some(stroke);
+        self
+    }
+
+    pub fn gradient(mut self, gradient: Gradient) -> Self {
+        self.node2d.gradient = Some(gradient);
+        self
+    }
+
+    /// Постоянное число отрезков на полный круг.
+    pub fn segments(mut self, n: usize) -> Self {
+        self.node2d.segments = Some(n.max(3));
+        self
+    }
+
+    /// Рисует поддерево в цель рендера вместо экрана.
+    pub fn viewport(mut self, target: &RenderTarget) -> Self {
+        self.node2d.viewport = Some(*target);
+        self
+    }
+
     pub fn keep(mut self, keep: Keep) -> Self {
         self.node2d.keep = keep;
         self
     }
 
+    pub fn hit(mut self, hit: Hit) -> Self {
+        self.node2d.hit = hit;
+        self
+    }
+
+    pub fn occluder(mut self, occluder: Occluder) -> Self {
+        self.node2d.occluder = Some(occluder);
+        self
+    }
+
+    pub fn input(mut self, input: Input) -> Self {
+        self.node2d.input = input;
+        self
+    }
+
+    pub fn gestures(mut self, mode: GestureMode) -> Self {
+        self.node2d.gestures = mode;
+        self
+    }
+
+    pub fn draggable(mut self, drag: Drag) -> Self {
+        self.node2d.drag = Some(drag);
+        self
+    }
+
+    pub fn drop_target(mut self, sel: bool) -> Self {
+        self.node2d.drop_target = sel;
+        self
+    }
+
+    pub fn anchor(mut self, anchor: Anchor) -> Self {
+        self.node2d.anchor = Some(anchor);
+        self
+    }
+
     pub fn offset(mut self, x: f32, y: f32) -> Self {
         self.node2d.offset = vec2(x, y);
         self
     }
 
+    /// Запускает анимацию спрайта.
+    pub fn play(mut self, animation: &str) -> Self {
+        self.node2d.obj.play(animation);
+        self
+    }
+
     pub fn get_node(self) -> Node2d {
         self.node2d
     }
@@ -510,12 +1770,51 @@ pub fn circle(name: &str, r: f32) -> CreateNode2d {
     CreateNode2d::new(name, Obj2d::Circle(r))
 }
 
+#[inline(always)]
+pub fn ellipse(name: &str, rx: f32, ry: f32) -> CreateNode2d {
+    CreateNode2d::new(name, Obj2d::Ellipse(rx, ry))
+}
+
+/// Дуга от угла `start` до `end` в радианах, по часовой стрелке на экране.
+#[inline(always)]
+pub fn arc(name: &str, r: f32, start: f32, end: f32, line: Line) -> CreateNode2d {
+    CreateNode2d::new(name, Obj2d::Arc(r, start, end, line))
+}
+
+#[inline(always)]
+pub fn pie(name: &str, r: f32, start: f32, end: f32) -> CreateNode2d {
+    CreateNode2d::new(name, Obj2d::Pie(r, start, end))
+}
+
+#[inline(always)]
+pub fn ring(name: &str, r: f32, inner: f32) -> CreateNode2d {
+    CreateNode2d::new(name, Obj2d::Ring(r, inner.min(r)))
+}
+
 #[inline(always)]
 pub fn rect(name: &str, w: f32, h: f32, r: f32) -> CreateNode2d {
     let r = r.min((w / 2.).min(h / 2.));
     CreateNode2d::new(name, Obj2d::Rect(w, h, r))
 }
 
+/// Узел, который показывает цель рендера и рисует в неё своих детей.
+#[inline(always)]
+pub fn viewport(name: &str, target: &RenderTarget) -> CreateNode2d {
+    CreateNode2d::new(name, Obj2d::Texture(target.texture())).viewport(target)
+}
+
+#[inline(always)]
+pub fn nine_slice(name: &str, slice: &NineSlice, w: f32, h: f32) -> CreateNode2d {
+    CreateNode2d::new(
+        name,
+        Obj2d::NineSlice(NineSlice {
+            width: w,
+            height: h,
+            ..*slice
+        }),
+    )
+}
+
 pub fn line(
     name: &str,
     x1: f32,
@@ -539,33 +1838,91 @@ pub fn line(
         .rotation(angle)
 }
 
+#[inline(always)]
+pub fn polygon(name: &str, points: &[Vec2]) -> CreateNode2d {
+    CreateNode2d::new(name, Obj2d::Polygon(points.to_vec()))
+}
+
+#[inline(always)]
+pub fn polyline(name: &str, points: &[Vec2], line: Line) -> CreateNode2d {
+    CreateNode2d::new(name, Obj2d::Polyline(points.to_vec(), line))
+}
+
+/// Узел со своей геометрией. uv вершин - в долях текстуры.
+#[inline(always)]
+pub fn mesh(
+    name: &str,
+    vertices: Vec<(Vec2, Rgba, Vec2)>,
+    indices: Vec<u16>,
+    texture: Option<&Texture>,
+) -> CreateNode2d {
+    CreateNode2d::new(
+        name,
+        Obj2d::Mesh {
+            vertices,
+            indices,
+            texture: texture.copied(),
+        },
+    )
+}
+
+/// Залитая фигура по пути из отрезков и кривых.
+#[inline(always)]
+pub fn path(name: &str, path: &Path) -> CreateNode2d {
+    polygon(name, path.points())
+}
+
 #[inline(always)]
 pub fn image(name: &str, texture: &Texture) -> CreateNode2d {
     CreateNode2d::new(
         name,
-        Obj2d::Texture(Texture {
-            id: texture.id,
-            width: texture.width,
-            height: texture.height,
-        }),
+        Obj2d::Texture(*texture),
     )
 }
 
+#[inline(always)]
+pub fn particles(name: &str, particles: &Particles) -> CreateNode2d {
+    CreateNode2d::new(name, Obj2d::Particles(particles.clone()))
+}
+
+#[inline(always)]
+pub fn tilemap(name: &str, map: &TileMap) -> CreateNode2d {
+    CreateNode2d::new(name, Obj2d::TileMap(map.clone()))
+}
+
+/// Источник света. Виден только при узле `lighting` в сцене.
+#[inline(always)]
+pub fn light(name: &str, light: &Light) -> CreateNode2d {
+    CreateNode2d::new(name, Obj2d::Light(*light))
+        .material(&Material::light())
+        .blend(Blend::Additive)
+}
+
+/// Буфер света сцены, `ambient` - освещение без источников.
+/// Узел умножает на буфер всё, что нарисовано до него, поэтому
+/// интерфейс ставится после. Один буфер на сцену, `ambient` меняется
+/// через `RenderTarget::clear` цели из `Obj2d::Lighting`.
+pub fn lighting(name: &str, ambient: Rgba) -> CreateNode2d {
+    let size = unsafe { CANVAS } / 2.;
+    let target = RenderTarget::new(size.x, size.y).clear(Rgba { a: 1., ..ambient });
+
+    CreateNode2d::new(name, Obj2d::Lighting(target))
+        .color(Rgba::new(1., 1., 1., 1.))
+        .blend(Blend::Multiply)
+        .cull(false)
+}
+
+#[inline(always)]
+pub fn sprite(name: &str, sheet: &SpriteSheet) -> CreateNode2d {
+    CreateNode2d::new(name, Obj2d::Sprite(Sprite::new(sheet)))
+}
+
 #[inline(always)]
 pub fn text(name: &str, text: &str, size: f32, font: &Font) -> CreateNode2d {
-    let (id, w, h) = add_text(text, size, font.id, None);
+    let texture = add_text(text, size, font.id, None);
     CreateNode2d::new(
         name,
-        Obj2d::Text(
-            text.to_string(),
-            size,
-            font.id,
-            Texture {
-                id,
-                width: w,
-                height: h,
-            },
-        ),
+        Obj2d::Text(text.to_string(), size, font.id, texture),
     )
 }
 
@@ -578,6 +1935,23 @@ pub trait Module: Any {
             ON_TOUCH = true;
         }
     }
+    /// Указатель вошёл в узел, вышел из него или движется над ним.
+    fn pointer(&self, _obj: &mut Node2d, _id: u64, _pointer: &Pointer, _pos: Vec2) {}
+    fn gesture(&self, _obj: &mut Node2d, _gesture: &Gesture) {}
+    fn drag_start(&self, _obj: &mut Node2d, _pos: Vec2) {}
+    /// Груз над целью сброса, вызывается у цели.
+    fn drag_over(&self, _obj: &mut Node2d, _payload: Option<&(dyn Any + Send + Sync)>, _pos: Vec2) {}
+    /// Сброс на цель, `true` если груз принят.
+    fn drop(&self, _obj: &mut Node2d, _payload: Option<&(dyn Any + Send + Sync)>, _pos: Vec2) -> bool {
+        false
+    }
+    fn drag_end(&self, _obj: &mut Node2d, _accepted: bool) {}
+    /// Анимация спрайта закончилась (Once) или сменилась следующей из очереди.
+    fn animation_finished(&self, _obj: &mut Node2d, _name: &str) {}
+    /// Событие по пути от корня к цели, до самой цели.
+    fn capture(&self, _obj: &mut Node2d, _id: u64, _touch: &Touch, _pos: Vec2) {}
+    /// Событие, обработанное потомком, на пути обратно к корню.
+    fn bubble(&self, _obj: &mut Node2d, _id: u64, _touch: &Touch, _pos: Vec2) {}
 }
 
 #[macro_export]
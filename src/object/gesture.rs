@@ -0,0 +1,294 @@
+use super::Touch;
+use crate::render::{d2::world_to_screen, LAST_FRAME_TIME};
+
+use glam::Vec2;
+use std::{f32::consts::{PI, TAU}, mem::take};
+
+/// Направление свайпа.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Swipe {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// Распознанный жест, позиции в координатах мира.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Gesture {
+    Tap(Vec2),
+    DoubleTap(Vec2),
+    LongPress(Vec2),
+    /// Начало свайпа, направление и скорость (пикселей экрана в секунду).
+    Swipe(Vec2, Swipe, Vec2),
+    /// Текущая позиция и смещение за событие.
+    Pan(Vec2, Vec2),
+    /// Центр и масштаб относительно начала щипка.
+    Pinch(Vec2, f32),
+    /// Центр и угол поворота относительно начала жеста.
+    Rotate(Vec2, f32),
+}
+
+impl Gesture {
+    pub fn pos(&self) -> Vec2 {
+        match *self {
+            Gesture::Tap(p)
+            | Gesture::DoubleTap(p)
+            | Gesture::LongPress(p)
+            | Gesture::Swipe(p, _, _)
+            | Gesture::Pan(p, _)
+            | Gesture::Pinch(p, _)
+            | Gesture::Rotate(p, _) => p,
+        }
+    }
+}
+
+/// Кому доставлять жесты: никому, всем подписчикам или по области узла.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GestureMode {
+    Off,
+    Global,
+    Hit,
+}
+
+/// Пороги распознавания: время в секундах, расстояния в пикселях экрана.
+#[derive(Clone, Copy, Debug)]
+pub struct GestureConfig {
+    pub tap_time: f32,
+    pub tap_distance: f32,
+    pub double_tap_time: f32,
+    pub long_press_time: f32,
+    pub swipe_velocity: f32,
+}
+
+impl GestureConfig {
+    pub const fn new() -> Self {
+        Self {
+            tap_time: 0.25,
+            tap_distance: 10.,
+            double_tap_time: 0.3,
+            long_press_time: 0.5,
+            swipe_velocity: 800.,
+        }
+    }
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Pointer {
+    id: u64,
+    start: Vec2,
+    pos: Vec2,
+    screen_start: Vec2,
+    screen: Vec2,
+    time: f64,
+    /// Позиция в конце прошлого кадра и время того кадра: от них считается
+    /// скорость, даже если за кадр пришло несколько движений.
+    last_screen: Vec2,
+    last_time: f64,
+    /// Кадр последнего движения.
+    frame: f64,
+    velocity: Vec2,
+    moved: bool,
+    long: bool,
+    /// Палец участвовал в щипке: после него нет ни тапа, ни свайпа.
+    pinched: bool,
+}
+
+/// Щипок и поворот двумя первыми пальцами. `dist` и `angle` - опора,
+/// от которой считаются масштаб и накопленный угол.
+struct Pinch {
+    dist: f32,
+    angle: f32,
+    scale: f32,
+    rotation: f32,
+}
+
+pub(crate) static mut GESTURE_CONFIG: GestureConfig = GestureConfig::new();
+
+static mut POINTERS: Vec<Pointer> = Vec::new();
+static mut LAST_TAP: Option<(f64, Vec2)> = None;
+static mut PINCH: Option<Pinch> = None;
+static mut GESTURES: Vec<Gesture> = Vec::new();
+
+/// Расстояние и угол между двумя первыми пальцами, и их центр.
+#[inline(always)]
+fn two_fingers(pointers: &[Pointer]) -> (f32, f32, Vec2) {
+    let d = pointers[1].screen - pointers[0].screen;
+    (d.length(), d.y.atan2(d.x), (pointers[0].pos + pointers[1].pos) / 2.)
+}
+
+/// Новая опора щипка после смены набора пальцев, без скачка масштаба и угла.
+fn rebase(pointers: &mut [Pointer]) {
+    if pointers.len() < 2 {
+        unsafe {
+            PINCH = None;
+        }
+        return;
+    }
+
+    for p in pointers.iter_mut() {
+        p.moved = true;
+        p.pinched = true;
+    }
+
+    let (dist, angle, _) = two_fingers(pointers);
+
+    unsafe {
+        match &mut PINCH {
+            Some(pinch) => {
+                pinch.dist = dist / pinch.scale.max(f32::EPSILON);
+                pinch.angle = angle;
+            }
+            None => {
+                PINCH = Some(Pinch {
+                    dist,
+                    angle,
+                    scale: 1.,
+                    rotation: 0.,
+                })
+            }
+        }
+    }
+}
+
+pub(crate) fn feed(id: u64, touch: &Touch, pos: Vec2) {
+    unsafe {
+        let config = GESTURE_CONFIG;
+        let now = LAST_FRAME_TIME;
+        let screen = world_to_screen(pos);
+        let pointers = &mut POINTERS;
+
+        match touch {
+            Touch::Press => {
+                pointers.retain(|p| p.id != id);
+                pointers.push(Pointer {
+                    id,
+                    start: pos,
+                    pos,
+                    screen_start: screen,
+                    screen,
+                    time: now,
+                    last_screen: screen,
+                    last_time: now,
+                    frame: now,
+                    velocity: Vec2::ZERO,
+                    moved: false,
+                    long: false,
+                    pinched: false,
+                });
+
+                // Второй палец: тапы отменяются, начинается щипок
+                rebase(pointers);
+            }
+            Touch::Move => {
+                let p = match pointers.iter_mut().find(|p| p.id == id) {
+                    Some(p) => p,
+                    None => return,
+                };
+
+                let delta = pos - p.pos;
+
+                if now > p.frame {
+                    p.last_screen = p.screen;
+                    p.last_time = p.frame;
+                    p.frame = now;
+                }
+
+                let dt = (now - p.last_time) as f32;
+
+                if dt > 0. {
+                    p.velocity = (screen - p.last_screen) / dt;
+                }
+
+                p.pos = pos;
+                p.screen = screen;
+
+                if p.screen.distance(p.screen_start) > config.tap_distance {
+                    p.moved = true;
+                }
+
+                if pointers.len() == 1 {
+                    if pointers[0].moved {
+                        GESTURES.push(Gesture::Pan(pos, delta));
+                    }
+                } else if let Some(pinch) = &mut PINCH {
+                    let (d, a, center) = two_fingers(pointers);
+
+                    if pinch.dist > 0. {
+                        pinch.scale = d / pinch.dist;
+                        GESTURES.push(Gesture::Pinch(center, pinch.scale));
+                    }
+
+                    // atan2 прыгает на 2PI при переходе через -PI..PI
+                    pinch.rotation += wrap(a - pinch.angle);
+                    pinch.angle = a;
+                    GESTURES.push(Gesture::Rotate(center, pinch.rotation));
+                }
+            }
+            Touch::Relese => {
+                let i = match pointers.iter().position(|p| p.id == id) {
+                    Some(i) => i,
+                    None => return,
+                };
+                let p = pointers.remove(i);
+                rebase(pointers);
+
+                if PINCH.is_none() && !p.moved && !p.long && ((now - p.time) as f32) < config.tap_time {
+                    match LAST_TAP {
+                        Some((time, at))
+                            if ((now - time) as f32) < config.double_tap_time
+                                && at.distance(screen) < config.tap_distance * 2. =>
+                        {
+                            LAST_TAP = None;
+                            GESTURES.push(Gesture::DoubleTap(pos));
+                        }
+                        _ => {
+                            LAST_TAP = Some((now, screen));
+                            GESTURES.push(Gesture::Tap(pos));
+                        }
+                    }
+                } else if p.moved && !p.pinched && pointers.is_empty() && p.velocity.length() >= config.swipe_velocity {
+                    let v = p.velocity;
+                    let direction = if v.x.abs() > v.y.abs() {
+                        if v.x > 0. { Swipe::Right } else { Swipe::Left }
+                    } else if v.y > 0. {
+                        Swipe::Down
+                    } else {
+                        Swipe::Up
+                    };
+
+                    GESTURES.push(Gesture::Swipe(p.start, direction, v));
+                }
+            }
+        }
+    }
+}
+
+/// Угол в -PI..PI.
+#[inline(always)]
+fn wrap(a: f32) -> f32 {
+    (a + PI).rem_euclid(TAU) - PI
+}
+
+/// Долгое нажатие ловится по времени, без событий касания.
+pub(crate) fn update() -> Vec<Gesture> {
+    unsafe {
+        let config = GESTURE_CONFIG;
+
+        if POINTERS.len() == 1 {
+            let p = &mut POINTERS[0];
+
+            if !p.moved && !p.long && ((LAST_FRAME_TIME - p.time) as f32) >= config.long_press_time {
+                p.long = true;
+                GESTURES.push(Gesture::LongPress(p.pos));
+            }
+        }
+
+        take(&mut GESTURES)
+    }
+}
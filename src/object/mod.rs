@@ -1,4 +1,14 @@
+use glam::{vec2, Vec2};
+use std::{any::Any, sync::Arc};
+
+pub mod aseprite;
 pub mod d2;
+pub mod gesture;
+pub mod particles;
+pub mod prefab;
+pub mod sprite;
+pub mod tiled;
+pub mod tilemap;
 
 pub enum Keep {
     Canvas,
@@ -13,12 +23,166 @@ pub enum Keep {
     RightDown,
 }
 
+/// Растягивание узла по осям при раскладке по якорям.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Stretch {
+    None,
+    Width,
+    Height,
+    Both,
+}
+
+/// Якоря и отступы относительно прямоугольника родителя (или холста).
+///
+/// `min`/`max` в долях 0..1 размера родителя, `margin` в пикселях:
+/// слева, сверху, справа, снизу.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Anchor {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub margin: [f32; 4],
+    pub stretch: Stretch,
+}
+
+impl Anchor {
+    pub fn new(min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Self {
+        Self {
+            min: vec2(min_x, min_y),
+            max: vec2(max_x, max_y),
+            margin: [0.; 4],
+            stretch: Stretch::None,
+        }
+    }
+
+    /// Привязка к одной точке без растягивания.
+    pub fn point(x: f32, y: f32) -> Self {
+        Self::new(x, y, x, y)
+    }
+
+    /// Заполнить весь прямоугольник родителя.
+    pub fn fill() -> Self {
+        Self::new(0., 0., 1., 1.).stretch(Stretch::Both)
+    }
+
+    pub fn margin(mut self, left: f32, up: f32, right: f32, down: f32) -> Self {
+        self.margin = [left, up, right, down];
+        self
+    }
+
+    pub fn stretch(mut self, stretch: Stretch) -> Self {
+        self.stretch = stretch;
+        self
+    }
+}
+
 pub enum Touch {
     Press,
     Relese,
     Move,
 }
 
+/// Наведение указателя на узел, приходит в `Module::pointer`.
+pub enum Pointer {
+    Enter,
+    Exit,
+    Hover,
+}
+
+/// Ось, вдоль которой можно перетаскивать узел.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Axis {
+    Both,
+    X,
+    Y,
+}
+
+/// Настройки перетаскивания узла.
+///
+/// `bounds` ограничивает позицию в координатах родителя, `ghost` тащит
+/// полупрозрачную копию вместо самого узла, `snap_back` возвращает узел
+/// на место, если цель не приняла груз.
+#[derive(Clone)]
+pub struct Drag {
+    pub axis: Axis,
+    pub bounds: Option<(Vec2, Vec2)>,
+    pub ghost: bool,
+    pub snap_back: bool,
+    pub payload: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl Drag {
+    pub fn new() -> Self {
+        Self {
+            axis: Axis::Both,
+            bounds: None,
+            ghost: false,
+            snap_back: true,
+            payload: None,
+        }
+    }
+
+    pub fn axis(mut self, axis: Axis) -> Self {
+        self.axis = axis;
+        self
+    }
+
+    pub fn bounds(mut self, x1: f32, y1: f32, x2: f32, y2: f32) -> Self {
+        self.bounds = Some((vec2(x1, y1), vec2(x2, y2)));
+        self
+    }
+
+    pub fn ghost(mut self, sel: bool) -> Self {
+        self.ghost = sel;
+        self
+    }
+
+    pub fn snap_back(mut self, sel: bool) -> Self {
+        self.snap_back = sel;
+        self
+    }
+
+    pub fn payload<T: 'static + Send + Sync>(mut self, value: T) -> Self {
+        self.payload = Some(Arc::new(value));
+        self
+    }
+}
+
+impl Default for Drag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Форма попадания касания. `Bounds` берёт границы объекта,
+/// `Alpha` проверяет прозрачность текстуры под точкой (порог 0..1),
+/// остальные заменяют границы своей фигурой в локальных координатах.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Hit {
+    Bounds,
+    Alpha(f32),
+    Circle(f32),
+    Rect(f32, f32),
+    Polygon(Vec<Vec2>),
+}
+
+/// Форма, отбрасывающая тень от источников света,
+/// в локальных координатах узла от центра объекта.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Occluder {
+    Rect(f32, f32),
+    Circle(f32),
+    Polygon(Vec<Vec2>),
+}
+
+/// Участие узла в касаниях: `Auto` только при наличии скриптов,
+/// `Block` всегда перехватывает, `Pass` пропускает насквозь.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Input {
+    Auto,
+    Block,
+    Pass,
+}
+
 pub enum Key {
     Char(char),
     //Code(KeyCode),
@@ -0,0 +1,286 @@
+use crate::render::{Rgba, Texture};
+
+use glam::{vec2, Affine2, Vec2};
+use std::{f32::consts::TAU, mem::take};
+
+static mut SEED: u32 = 0x9E37_79B9;
+
+/// Область, из которой вылетают частицы.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Emitter {
+    Point,
+    Circle(f32),
+    Rect(f32, f32),
+}
+
+/// В чём живут частицы: `Local` движутся вместе с узлом,
+/// `World` остаются в мире, куда их выпустили (след за объектом).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ParticleSpace {
+    Local,
+    World,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct Particle {
+    pub(crate) position: Vec2,
+    velocity: Vec2,
+    age: f32,
+    life: f32,
+}
+
+impl Particle {
+    /// Доля прожитой жизни от 0 до 1.
+    #[inline(always)]
+    pub(crate) fn time(&self) -> f32 {
+        (self.age / self.life).min(1.)
+    }
+}
+
+/// Система частиц одного узла, рисуется одной пачкой.
+/// Без текстуры частица - квадрат или круг (`round`) размера `size`.
+#[derive(Clone, PartialEq)]
+pub struct Particles {
+    /// Частиц в секунду.
+    pub rate: f32,
+    /// Выпускать ли частицы по `rate`. Живые долетают в любом случае.
+    pub emitting: bool,
+    pub emitter: Emitter,
+    pub space: ParticleSpace,
+    pub lifetime: (f32, f32),
+    /// Разброс скорости по осям: от и до.
+    pub velocity: (Vec2, Vec2),
+    pub gravity: Vec2,
+    /// Затухание скорости в секунду.
+    pub damping: f32,
+    pub size: f32,
+    pub texture: Option<Texture>,
+    pub round: bool,
+    pub max: usize,
+    scale: Vec<(f32, f32)>,
+    color: Vec<(f32, Rgba)>,
+    pub(crate) particles: Vec<Particle>,
+    /// Границы живых частиц в их пространстве.
+    pub(crate) bounds: (Vec2, Vec2),
+    time: f32,
+    burst: usize,
+    seed: u32,
+}
+
+impl Particles {
+    pub fn new(rate: f32) -> Self {
+        Self {
+            rate,
+            emitting: true,
+            emitter: Emitter::Point,
+            space: ParticleSpace::Local,
+            lifetime: (1., 1.),
+            velocity: (vec2(-50., -50.), vec2(50., 50.)),
+            gravity: Vec2::ZERO,
+            damping: 0.,
+            size: 8.,
+            texture: None,
+            round: false,
+            max: 1000,
+            scale: Vec::new(),
+            color: Vec::new(),
+            particles: Vec::new(),
+            bounds: (Vec2::ZERO, Vec2::ZERO),
+            time: 0.,
+            burst: 0,
+            seed: unsafe {
+                SEED = SEED.wrapping_mul(747_796_405).wrapping_add(2_891_336_453) | 1;
+                SEED
+            },
+        }
+    }
+
+    pub fn emitter(mut self, emitter: Emitter) -> Self {
+        self.emitter = emitter;
+        self
+    }
+
+    pub fn space(mut self, space: ParticleSpace) -> Self {
+        self.space = space;
+        self
+    }
+
+    /// Время жизни в секундах, случайное от `min` до `max`.
+    pub fn lifetime(mut self, min: f32, max: f32) -> Self {
+        self.lifetime = (min.max(0.001), max.max(min).max(0.001));
+        self
+    }
+
+    pub fn velocity(mut self, min: Vec2, max: Vec2) -> Self {
+        self.velocity = (min, max);
+        self
+    }
+
+    pub fn gravity(mut self, x: f32, y: f32) -> Self {
+        self.gravity = vec2(x, y);
+        self
+    }
+
+    pub fn damping(mut self, damping: f32) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    pub fn size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Множитель размера по доле жизни: пары (0..1, масштаб).
+    pub fn scale(mut self, keys: &[(f32, f32)]) -> Self {
+        self.scale = keys.to_vec();
+        self.scale.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self
+    }
+
+    /// Цвет по доле жизни: пары (0..1, цвет).
+    pub fn color(mut self, keys: &[(f32, Rgba)]) -> Self {
+        self.color = keys.to_vec();
+        self.color.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self
+    }
+
+    pub fn texture(mut self, texture: &Texture) -> Self {
+        self.texture = Some(*texture);
+        self
+    }
+
+    pub fn round(mut self, round: bool) -> Self {
+        self.round = round;
+        self
+    }
+
+    /// Предел живых частиц.
+    pub fn max(mut self, max: usize) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Выпускает `count` частиц в следующем кадре.
+    pub fn burst(&mut self, count: usize) {
+        self.burst += count;
+    }
+
+    pub fn count(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    /// Масштаб частицы в доле жизни `t`.
+    pub(crate) fn scale_at(&self, t: f32) -> f32 {
+        sample(&self.scale, t, 1., |a, b, k| a + (b - a) * k)
+    }
+
+    pub(crate) fn color_at(&self, t: f32) -> [f32; 4] {
+        let color = sample(&self.color, t, Rgba::new(1., 1., 1., 1.), |a, b, k| {
+            Rgba::new(
+                a.r + (b.r - a.r) * k,
+                a.g + (b.g - a.g) * k,
+                a.b + (b.b - a.b) * k,
+                a.a + (b.a - a.a) * k,
+            )
+        });
+        color.get()
+    }
+
+    /// Вершин на одну частицу.
+    pub(crate) fn vertices(&self) -> usize {
+        if self.round && self.texture.is_none() { 9 } else { 4 }
+    }
+
+    /// Двигает и выпускает частицы. Возвращает, изменилось ли что-то на экране.
+    pub(crate) fn advance(&mut self, delta: f32, transform: &Affine2) -> bool {
+        let was = !self.particles.is_empty();
+
+        let gravity = self.gravity * delta;
+        let damping = (-self.damping * delta).exp();
+
+        self.particles.retain_mut(|p| {
+            p.age += delta;
+            p.velocity = (p.velocity + gravity) * damping;
+            p.position += p.velocity * delta;
+            p.age < p.life
+        });
+
+        let mut count = take(&mut self.burst);
+        if self.emitting && self.rate > 0. {
+            self.time += delta * self.rate;
+            count += self.time as usize;
+            self.time = self.time.fract();
+        }
+
+        count = count.min(self.max.saturating_sub(self.particles.len()));
+
+        for _ in 0..count {
+            let particle = self.spawn(transform);
+            self.particles.push(particle);
+        }
+
+        self.bounds = self.particles.iter().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(lo, hi), p| (lo.min(p.position), hi.max(p.position)),
+        );
+        let half = Vec2::splat(self.size * self.scale.iter().fold(1f32, |m, k| m.max(k.1)) / 2.);
+        self.bounds = (self.bounds.0 - half, self.bounds.1 + half);
+
+        was || !self.particles.is_empty()
+    }
+
+    fn spawn(&mut self, transform: &Affine2) -> Particle {
+        let position = match self.emitter {
+            Emitter::Point => Vec2::ZERO,
+            Emitter::Circle(r) => {
+                // Корень из случайного числа - равномерно по площади круга
+                let angle = self.random() * TAU;
+                vec2(angle.cos(), angle.sin()) * r * self.random().sqrt()
+            }
+            Emitter::Rect(w, h) => vec2(self.random() - 0.5, self.random() - 0.5) * vec2(w, h),
+        };
+
+        let (min, max) = self.velocity;
+        let velocity = min + (max - min) * vec2(self.random(), self.random());
+        let life = self.lifetime.0 + (self.lifetime.1 - self.lifetime.0) * self.random();
+
+        let (position, velocity) = match self.space {
+            ParticleSpace::Local => (position, velocity),
+            ParticleSpace::World => (
+                transform.transform_point2(position),
+                transform.transform_vector2(velocity),
+            ),
+        };
+
+        Particle {
+            position,
+            velocity,
+            age: 0.,
+            life,
+        }
+    }
+
+    /// Xorshift: своя последовательность у каждой системы.
+    fn random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        (self.seed >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+/// Значение кривой по ключам, отсортированным по времени.
+fn sample<T: Copy>(keys: &[(f32, T)], t: f32, default: T, lerp: fn(T, T, f32) -> T) -> T {
+    let i = keys.partition_point(|(k, _)| *k <= t);
+
+    match (i.checked_sub(1).map(|i| keys[i]), keys.get(i).copied()) {
+        (Some((t0, a)), Some((t1, b))) => lerp(a, b, (t - t0) / (t1 - t0).max(f32::EPSILON)),
+        (Some((_, v)), None) | (None, Some((_, v))) => v,
+        (None, None) => default,
+    }
+}
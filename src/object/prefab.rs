@@ -0,0 +1,55 @@
+use super::d2::CreateNode2d;
+
+use std::{collections::HashMap, sync::Arc};
+
+static mut PREFABS: Option<HashMap<String, Prefab>> = None;
+
+/// Шаблон поддерева узлов со скриптами и данными.
+///
+/// Каждый вызов `instance` заново строит поддерево, поэтому экземпляры
+/// независимы, а текстуры и шрифты (это только id) остаются общими.
+#[derive(Clone)]
+pub struct Prefab {
+    build: Arc<dyn Fn() -> CreateNode2d + Send + Sync>,
+}
+
+impl Prefab {
+    pub fn new(build: impl Fn() -> CreateNode2d + Send + Sync + 'static) -> Self {
+        Self {
+            build: Arc::new(build),
+        }
+    }
+
+    /// Новый экземпляр с именем `name`, остальное можно переопределить
+    /// обычными методами `CreateNode2d` (`position`, `scale`, `hash`...).
+    pub fn instance(&self, name: &str) -> CreateNode2d {
+        (self.build)().name(name)
+    }
+
+    pub fn instances(&self, name: &str, count: usize) -> Vec<CreateNode2d> {
+        (0..count)
+            .map(|i| self.instance(&format!("{}{}", name, i)))
+            .collect()
+    }
+}
+
+#[inline(always)]
+pub fn prefab(build: impl Fn() -> CreateNode2d + Send + Sync + 'static) -> Prefab {
+    Prefab::new(build)
+}
+
+/// Регистрирует префаб по имени. Объекты карт Tiled с таким классом
+/// создаются из этого префаба.
+pub fn set_prefab(key: &str, prefab: Prefab) {
+    unsafe {
+        if PREFABS.is_none() {
+            PREFABS = Some(HashMap::new());
+        }
+        PREFABS.as_mut().unwrap().insert(key.to_string(), prefab);
+    }
+}
+
+#[inline(always)]
+pub fn get_prefab(key: &str) -> Option<&'static Prefab> {
+    unsafe { PREFABS.as_ref()?.get(key) }
+}
@@ -0,0 +1,270 @@
+use crate::render::Texture;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+/// Как анимация ведёт себя после последнего кадра.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Playback {
+    Loop,
+    Once,
+    PingPong,
+}
+
+#[derive(Clone)]
+struct Animation {
+    /// Номер кадра листа и его длительность в секундах.
+    frames: Vec<(usize, f32)>,
+    playback: Playback,
+}
+
+#[derive(Clone)]
+struct Sheet {
+    frames: Vec<Texture>,
+    animations: HashMap<String, Animation>,
+}
+
+/// Лист спрайтов: кадры из сетки или списка областей и именованные анимации.
+/// Клонирование дешёвое, узлы делят один лист.
+#[derive(Clone)]
+pub struct SpriteSheet(Arc<Sheet>);
+
+impl SpriteSheet {
+    /// Кадры сеткой `columns` x `rows`, слева направо и сверху вниз.
+    pub fn grid(texture: &Texture, columns: u32, rows: u32) -> Self {
+        if columns == 0 || rows == 0 {
+            return Self::frames(texture, &[]);
+        }
+
+        let w = texture.width / columns as f32;
+        let h = texture.height / rows as f32;
+
+        let rects: Vec<_> = (0..rows)
+            .flat_map(|y| (0..columns).map(move |x| (x as f32 * w, y as f32 * h, w, h)))
+            .collect();
+
+        Self::frames(texture, &rects)
+    }
+
+    /// Кадры по областям текстуры в пикселях: (x, y, ширина, высота).
+    /// Без областей единственным кадром становится вся текстура.
+    pub fn frames(texture: &Texture, rects: &[(f32, f32, f32, f32)]) -> Self {
+        let mut frames: Vec<_> = rects
+            .iter()
+            .map(|&(x, y, w, h)| texture.region(x, y, w, h))
+            .collect();
+
+        if frames.is_empty() {
+            frames.push(*texture);
+        }
+
+        Self(Arc::new(Sheet {
+            frames,
+            animations: HashMap::new(),
+        }))
+    }
+
+    /// Анимация с одинаковой длительностью кадров.
+    pub fn animation(self, name: &str, frames: &[usize], fps: f32, playback: Playback) -> Self {
+        let frames: Vec<_> = frames.iter().map(|&f| (f, 1. / fps)).collect();
+        self.animation_timed(name, &frames, playback)
+    }
+
+    /// Анимация со своей длительностью каждого кадра в секундах.
+    /// Кадры вне листа пропускаются, анимация без кадров не добавляется.
+    pub fn animation_timed(mut self, name: &str, frames: &[(usize, f32)], playback: Playback) -> Self {
+        let len = self.0.frames.len();
+        let frames: Vec<_> = frames
+            .iter()
+            .filter(|&&(f, _)| f < len)
+            .map(|&(f, time)| (f, time.max(0.001)))
+            .collect();
+
+        if frames.is_empty() {
+            return self;
+        }
+
+        Arc::make_mut(&mut self.0)
+            .animations
+            .insert(name.to_string(), Animation { frames, playback });
+        self
+    }
+}
+
+/// Анимированный спрайт, текущий кадр хранится готовой текстурой.
+#[derive(Clone)]
+pub struct Sprite {
+    sheet: SpriteSheet,
+    pub(crate) texture: Texture,
+    animation: Option<String>,
+    step: usize,
+    time: f32,
+    back: bool,
+    playing: bool,
+    queue: VecDeque<String>,
+    /// Множитель скорости проигрывания.
+    pub speed: f32,
+}
+
+impl PartialEq for Sprite {
+    // Для перерисовки важен только показываемый кадр
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.sheet.0, &other.sheet.0) && self.texture == other.texture
+    }
+}
+
+impl Sprite {
+    pub fn new(sheet: &SpriteSheet) -> Self {
+        Self {
+            sheet: sheet.clone(),
+            texture: sheet.0.frames[0],
+            animation: None,
+            step: 0,
+            time: 0.,
+            back: false,
+            playing: false,
+            queue: VecDeque::new(),
+            speed: 1.,
+        }
+    }
+
+    /// Запускает анимацию сначала, если играет другая или стоит. Очищает очередь.
+    /// Неизвестное имя ничего не меняет.
+    pub fn play(&mut self, name: &str) {
+        if !self.sheet.0.animations.contains_key(name) {
+            return;
+        }
+
+        self.queue.clear();
+
+        if self.playing && self.animation.as_deref() == Some(name) {
+            return;
+        }
+
+        self.start(name);
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    /// Анимация после окончания текущей (для Loop и PingPong - после цикла).
+    pub fn queue(&mut self, name: &str) {
+        if !self.sheet.0.animations.contains_key(name) {
+            return;
+        }
+
+        if self.playing {
+            self.queue.push_back(name.to_string());
+        } else {
+            self.start(name);
+        }
+    }
+
+    /// Показывает кадр листа, останавливая анимацию. Кадр вне листа пропускается.
+    pub fn set_frame(&mut self, frame: usize) {
+        if let Some(texture) = self.sheet.0.frames.get(frame) {
+            self.playing = false;
+            self.texture = texture.flags(&self.texture);
+        }
+    }
+
+    /// Отражение, сохраняется при смене кадров.
+    pub fn flip(&mut self, x: bool, y: bool) {
+        self.texture.flip_x = x;
+        self.texture.flip_y = y;
+    }
+
+    pub fn animation(&self) -> Option<&str> {
+        self.animation.as_deref()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    fn start(&mut self, name: &str) {
+        let Some(anim) = self.sheet.0.animations.get(name) else {
+            return;
+        };
+
+        self.texture = self.sheet.0.frames[anim.frames[0].0].flags(&self.texture);
+        self.animation = Some(name.to_string());
+        self.step = 0;
+        self.time = 0.;
+        self.back = false;
+        self.playing = true;
+    }
+
+    /// Продвигает анимацию, возвращает имя завершившейся.
+    pub(crate) fn advance(&mut self, delta: f32) -> Option<String> {
+        if !self.playing {
+            return None;
+        }
+
+        let sheet = self.sheet.0.clone();
+        let anim = &sheet.animations[self.animation.as_ref()?];
+        let len = anim.frames.len();
+
+        self.time += delta * self.speed;
+
+        let mut finished = false;
+
+        while self.time >= anim.frames[self.step].1 {
+            self.time -= anim.frames[self.step].1;
+
+            let cycle = match anim.playback {
+                Playback::Loop => {
+                    self.step = (self.step + 1) % len;
+                    self.step == 0
+                }
+                Playback::Once => {
+                    if self.step + 1 < len {
+                        self.step += 1;
+                        false
+                    } else {
+                        self.playing = false;
+                        true
+                    }
+                }
+                Playback::PingPong => {
+                    if len > 1 {
+                        if self.back {
+                            self.step -= 1;
+                        } else {
+                            self.step += 1;
+                        }
+                        if self.step == 0 || self.step == len - 1 {
+                            self.back = self.step != 0;
+                        }
+                    }
+                    self.step == 0
+                }
+            };
+
+            if cycle && (!self.playing || !self.queue.is_empty()) {
+                finished = true;
+                break;
+            }
+        }
+
+        if !finished {
+            self.texture = sheet.frames[anim.frames[self.step].0].flags(&self.texture);
+            return None;
+        }
+
+        let name = self.animation.clone();
+
+        match self.queue.pop_front() {
+            Some(next) => self.start(&next),
+            None => {
+                self.time = 0.;
+                self.texture = sheet.frames[anim.frames[self.step].0].flags(&self.texture);
+            }
+        }
+
+        name
+    }
+}
@@ -0,0 +1,838 @@
+use super::{
+    d2::{ellipse, image, polygon, polyline, rect, tilemap, CreateNode2d, Obj2d},
+    prefab::get_prefab,
+    tilemap::{Tile, TileMap},
+};
+use crate::{
+    parse::{self, dir, join, read, Json, Xml},
+    render::{rgba, shape::{Line, Stroke}, try_texture, Rgba, Texture},
+};
+
+use glam::{vec2, Vec2};
+use std::collections::HashMap;
+
+/// Ключи `hash` из имён свойств живут всё время работы, одно имя - одна строка.
+static mut KEYS: Vec<&'static str> = Vec::new();
+
+const FLIP_X: u32 = 1 << 31;
+const FLIP_Y: u32 = 1 << 30;
+const FLIP_D: u32 = 1 << 29;
+const GID: u32 = 0x0FFF_FFFF;
+
+/// Цвет объектов Tiled по умолчанию.
+const OBJECTS: u32 = 0xA0A0A4;
+
+/// Значение свойства Tiled. В `hash` узла кладётся само значение:
+/// `bool`, `i64`, `f32`, `String`, `Rgba` или `u32` для ссылки на объект.
+#[derive(Clone, PartialEq, Debug)]
+enum Property {
+    Bool(bool),
+    Int(i64),
+    Float(f32),
+    String(String),
+    Color(Rgba),
+    Object(u32),
+}
+
+struct Tileset {
+    first: u32,
+    name: String,
+    texture: Texture,
+    tile: Vec2,
+    margin: f32,
+    spacing: f32,
+    /// Тайл и его кадры: (тайл, длительность в мс).
+    animations: Vec<(u32, Vec<(u32, f32)>)>,
+}
+
+impl Tileset {
+    fn map(&self) -> TileMap {
+        let mut map = TileMap::new(&self.texture, self.tile.x, self.tile.y).spacing(self.margin, self.spacing);
+
+        // У TileMap одна частота на анимацию: длинные кадры повторяются
+        for (tile, frames) in &self.animations {
+            let step = frames.iter().fold(f32::MAX, |m, f| m.min(f.1)).max(1.);
+            let frames: Vec<u32> = frames
+                .iter()
+                .flat_map(|&(id, duration)| std::iter::repeat_n(id, (duration / step).round().max(1.) as usize))
+                .collect();
+
+            map = map.animation(*tile, &frames, 1000. / step);
+        }
+
+        map
+    }
+
+    /// Левый верхний угол тайла в текстуре.
+    fn source(&self, id: u32) -> Vec2 {
+        let columns = ((self.texture.width - self.margin * 2. + self.spacing) / (self.tile.x + self.spacing))
+            .floor()
+            .max(1.) as u32;
+
+        vec2((id % columns) as f32, (id / columns) as f32) * (self.tile + self.spacing) + self.margin
+    }
+}
+
+enum Shape {
+    Rect,
+    Ellipse,
+    Point,
+    Polygon(Vec<Vec2>),
+    Polyline(Vec<Vec2>),
+    Tile(u32),
+    Text(String),
+}
+
+struct Object {
+    id: u32,
+    name: String,
+    class: String,
+    position: Vec2,
+    size: Vec2,
+    rotation: f32,
+    visible: bool,
+    shape: Shape,
+    properties: Vec<(String, Property)>,
+}
+
+enum Kind {
+    /// Клетки (x, y, gid).
+    Tiles(Vec<(i32, i32, u32)>),
+    Objects(Vec<Object>, Rgba),
+    Image(Option<Texture>),
+    Group(Vec<Layer>),
+}
+
+struct Layer {
+    name: String,
+    visible: bool,
+    opacity: f32,
+    offset: Vec2,
+    tint: Rgba,
+    properties: Vec<(String, Property)>,
+    kind: Kind,
+}
+
+struct Map {
+    tile: Vec2,
+    tilesets: Vec<Tileset>,
+    layers: Vec<Layer>,
+    properties: Vec<(String, Property)>,
+}
+
+/// Загружает карту Tiled (TMX или JSON) через `load_file`.
+///
+/// Каждый слой - дочерний узел с именем слоя: слой тайлов становится
+/// `TileMap` (по узлу на набор, если их несколько, тайлы другого размера
+/// крепятся к клеткам левым нижним углом), слой объектов - узлами
+/// объектов, слой картинки - картинкой, группа - узлом со своими слоями.
+/// Видимость, прозрачность и смещение слоёв сохраняются. Свойства карты,
+/// слоёв и объектов лежат в `hash` под своими именами, у объектов ещё
+/// `id` (`u32`) и `class` (`String`). Объект, класс которого зарегистрирован
+/// через `set_prefab`, становится экземпляром префаба с позицией, поворотом
+/// и свойствами объекта. Поддерживаются только ортогональные карты.
+pub fn tiled(name: &str, path: &str) -> Result<CreateNode2d, String> {
+    let mut loader = Loader {
+        textures: HashMap::new(),
+    };
+
+    let text = read(path)?;
+    let map = if text.trim_start().starts_with('{') {
+        loader.json_map(&parse::json(&text)?, dir(path))?
+    } else {
+        loader.xml_map(&parse::xml(&text)?, dir(path))?
+    };
+
+    let layers = map
+        .layers
+        .iter()
+        .map(|layer| build(layer, &map))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(properties(CreateNode2d::new(name, Obj2d::None), &map.properties).node(layers))
+}
+
+fn build(layer: &Layer, map: &Map) -> Result<CreateNode2d, String> {
+    let node = match &layer.kind {
+        Kind::Tiles(cells) => {
+            let mut maps: Vec<(usize, TileMap)> = Vec::new();
+
+            for &(x, y, gid) in cells {
+                let Some((set, tile)) = find(&map.tilesets, gid) else { continue };
+
+                let i = match maps.iter().position(|(s, _)| *s == set) {
+                    Some(i) => i,
+                    None => {
+                        let tileset = &map.tilesets[set];
+                        maps.push((set, tileset.map().grid(map.tile.x, map.tile.y)));
+                        maps.len() - 1
+                    }
+                };
+
+                maps[i].1.set(x, y, tile);
+            }
+
+            match maps.len() {
+                0 => CreateNode2d::new(&layer.name, Obj2d::None),
+                1 => tilemap(&layer.name, &maps[0].1),
+                _ => CreateNode2d::new(&layer.name, Obj2d::None).node(
+                    maps.iter()
+                        .map(|(set, m)| tilemap(&map.tilesets[*set].name, m).color(Rgba { a: 1., ..layer.tint }))
+                        .collect(),
+                ),
+            }
+        }
+        Kind::Objects(objects, color) => CreateNode2d::new(&layer.name, Obj2d::None).node(
+            objects
+                .iter()
+                .map(|o| object(o, *color, &map.tilesets))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        Kind::Image(Some(texture)) => image(&layer.name, texture).offset(1., 1.),
+        Kind::Image(None) => CreateNode2d::new(&layer.name, Obj2d::None),
+        Kind::Group(layers) => CreateNode2d::new(&layer.name, Obj2d::None).node(
+            layers
+                .iter()
+                .map(|l| build(l, map))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+    };
+
+    // Прозрачность узла переходит детям, оттенок - только самому слою
+    let node = node
+        .position(layer.offset.x, layer.offset.y)
+        .visible(layer.visible)
+        .color(Rgba {
+            a: layer.opacity,
+            ..layer.tint
+        });
+
+    Ok(properties(node, &layer.properties))
+}
+
+fn object(o: &Object, color: Rgba, tilesets: &[Tileset]) -> Result<CreateNode2d, String> {
+    let fill = Rgba { a: 0.4, ..color };
+    let stroke = Stroke::new(1., color);
+
+    let prefab = get_prefab(&o.class);
+
+    let node = match &o.shape {
+        _ if prefab.is_some() => prefab.unwrap().instance(&o.name),
+        Shape::Rect if o.size != Vec2::ZERO => rect(&o.name, o.size.x, o.size.y, 0.)
+            .offset(1., 1.)
+            .color(fill)
+            .stroke(stroke),
+        Shape::Ellipse if o.size != Vec2::ZERO => ellipse(&o.name, o.size.x / 2., o.size.y / 2.)
+            .offset(1., 1.)
+            .color(fill)
+            .stroke(stroke),
+        Shape::Polygon(points) => polygon(&o.name, points).color(fill).stroke(stroke),
+        Shape::Polyline(points) => polyline(&o.name, points, Line::new(1.)).color(color),
+        Shape::Tile(gid) => {
+            let (set, tile) = find(tilesets, *gid).ok_or_else(|| format!("Object {}: unknown tile {gid}", o.id))?;
+            let tileset = &tilesets[set];
+            let source = tileset.source(tile.id);
+            let size = if o.size == Vec2::ZERO { tileset.tile } else { o.size };
+
+            let texture = tileset
+                .texture
+                .region(source.x, source.y, tileset.tile.x, tileset.tile.y)
+                .flip(tile.flip_x, tile.flip_y);
+
+            // Тайл-объект крепится левым нижним углом
+            image(&o.name, &texture)
+                .offset(1., -1.)
+                .scale(size.x / tileset.tile.x, size.y / tileset.tile.y)
+                .color(Rgba::new(1., 1., 1., 1.))
+        }
+        Shape::Text(text) => CreateNode2d::new(&o.name, Obj2d::None).hash("text", text.clone()),
+        Shape::Rect | Shape::Ellipse | Shape::Point => CreateNode2d::new(&o.name, Obj2d::None),
+    };
+
+    let mut node = node
+        .position(o.position.x, o.position.y)
+        .rotation(o.rotation.to_radians())
+        .visible(o.visible)
+        .hash("id", o.id);
+
+    if !o.class.is_empty() {
+        node = node.hash("class", o.class.clone());
+    }
+
+    Ok(properties(node, &o.properties))
+}
+
+fn properties(mut node: CreateNode2d, properties: &[(String, Property)]) -> CreateNode2d {
+    for (name, value) in properties {
+        let key = key(name);
+        node = match value.clone() {
+            Property::Bool(v) => node.hash(key, v),
+            Property::Int(v) => node.hash(key, v),
+            Property::Float(v) => node.hash(key, v),
+            Property::String(v) => node.hash(key, v),
+            Property::Color(v) => node.hash(key, v),
+            Property::Object(v) => node.hash(key, v),
+        };
+    }
+
+    node
+}
+
+fn key(name: &str) -> &'static str {
+    unsafe {
+        if let Some(key) = KEYS.iter().find(|k| **k == name) {
+            return key;
+        }
+
+        let key: &'static str = Box::leak(name.to_string().into_boxed_str());
+        KEYS.push(key);
+        key
+    }
+}
+
+/// Набор и тайл по gid с флагами отражения.
+fn find(tilesets: &[Tileset], gid: u32) -> Option<(usize, Tile)> {
+    let id = gid & GID;
+    if id == 0 {
+        return None;
+    }
+
+    let set = tilesets.iter().rposition(|t| t.first <= id)?;
+    let tile = Tile::new(id - tilesets[set].first)
+        .flip(gid & FLIP_X != 0, gid & FLIP_Y != 0)
+        .diagonal(gid & FLIP_D != 0);
+
+    Some((set, tile))
+}
+
+struct Loader {
+    textures: HashMap<String, Texture>,
+}
+
+impl Loader {
+    fn texture(&mut self, path: &str) -> Result<Texture, String> {
+        if let Some(texture) = self.textures.get(path) {
+            return Ok(*texture);
+        }
+
+        let texture = try_texture(path)?;
+        self.textures.insert(path.to_string(), texture);
+        Ok(texture)
+    }
+
+    fn json_map(&mut self, j: &Json, dir: &str) -> Result<Map, String> {
+        check(j.get("orientation").str())?;
+
+        let mut tilesets = Vec::new();
+        for t in j.get("tilesets").array() {
+            let first = t.get("firstgid").u32().unwrap_or(1);
+
+            tilesets.push(match t.get("source").str() {
+                Some(source) => self.external(&join(dir, source), first)?,
+                None => self.json_tileset(t, first, dir)?,
+            });
+        }
+        tilesets.sort_by_key(|t| t.first);
+
+        Ok(Map {
+            tile: vec2(
+                j.get("tilewidth").f32().unwrap_or(0.),
+                j.get("tileheight").f32().unwrap_or(0.),
+            ),
+            tilesets,
+            layers: self.json_layers(j.get("layers"), dir)?,
+            properties: json_properties(j.get("properties")),
+        })
+    }
+
+    fn xml_map(&mut self, e: &Xml, dir: &str) -> Result<Map, String> {
+        if e.name != "map" {
+            return Err(format!("Expected <map>, found <{}>", e.name));
+        }
+        check(e.attr("orientation"))?;
+
+        let mut tilesets = Vec::new();
+        for t in e.all("tileset") {
+            let first = t.u32("firstgid").unwrap_or(1);
+
+            tilesets.push(match t.attr("source") {
+                Some(source) => self.external(&join(dir, source), first)?,
+                None => self.xml_tileset(t, first, dir)?,
+            });
+        }
+        tilesets.sort_by_key(|t| t.first);
+
+        Ok(Map {
+            tile: vec2(e.f32("tilewidth").unwrap_or(0.), e.f32("tileheight").unwrap_or(0.)),
+            tilesets,
+            layers: self.xml_layers(e, dir)?,
+            properties: xml_properties(e),
+        })
+    }
+
+    /// Внешний набор .tsx или .json, картинка ищется рядом с ним.
+    fn external(&mut self, path: &str, first: u32) -> Result<Tileset, String> {
+        let text = read(path)?;
+
+        if text.trim_start().starts_with('{') {
+            self.json_tileset(&parse::json(&text)?, first, dir(path))
+        } else {
+            self.xml_tileset(&parse::xml(&text)?, first, dir(path))
+        }
+    }
+
+    fn json_tileset(&mut self, j: &Json, first: u32, dir: &str) -> Result<Tileset, String> {
+        let name = j.get("name").str().unwrap_or_default().to_string();
+        let image = j
+            .get("image")
+            .str()
+            .ok_or_else(|| format!("Tileset {name}: image collections are not supported"))?;
+
+        let animations = j
+            .get("tiles")
+            .array()
+            .iter()
+            .filter_map(|t| {
+                let frames: Vec<_> = t
+                    .get("animation")
+                    .array()
+                    .iter()
+                    .filter_map(|f| Some((f.get("tileid").u32()?, f.get("duration").f32()?)))
+                    .collect();
+
+                (!frames.is_empty()).then_some((t.get("id").u32()?, frames))
+            })
+            .collect();
+
+        Ok(Tileset {
+            first,
+            texture: self.texture(&join(dir, image))?,
+            tile: vec2(
+                j.get("tilewidth").f32().unwrap_or(0.),
+                j.get("tileheight").f32().unwrap_or(0.),
+            ),
+            margin: j.get("margin").f32().unwrap_or(0.),
+            spacing: j.get("spacing").f32().unwrap_or(0.),
+            animations,
+            name,
+        })
+    }
+
+    fn xml_tileset(&mut self, e: &Xml, first: u32, dir: &str) -> Result<Tileset, String> {
+        let name = e.attr("name").unwrap_or_default().to_string();
+        let image = e
+            .child("image")
+            .and_then(|i| i.attr("source"))
+            .ok_or_else(|| format!("Tileset {name}: image collections are not supported"))?;
+
+        let animations = e
+            .all("tile")
+            .filter_map(|t| {
+                let frames: Vec<_> = t
+                    .child("animation")?
+                    .all("frame")
+                    .filter_map(|f| Some((f.u32("tileid")?, f.f32("duration")?)))
+                    .collect();
+
+                (!frames.is_empty()).then_some((t.u32("id")?, frames))
+            })
+            .collect();
+
+        Ok(Tileset {
+            first,
+            texture: self.texture(&join(dir, image))?,
+            tile: vec2(e.f32("tilewidth").unwrap_or(0.), e.f32("tileheight").unwrap_or(0.)),
+            margin: e.f32("margin").unwrap_or(0.),
+            spacing: e.f32("spacing").unwrap_or(0.),
+            animations,
+            name,
+        })
+    }
+
+    fn json_layers(&mut self, layers: &Json, dir: &str) -> Result<Vec<Layer>, String> {
+        let mut out = Vec::new();
+
+        for l in layers.array() {
+            let kind = match l.get("type").str().unwrap_or_default() {
+                "tilelayer" => {
+                    let mut cells = Vec::new();
+                    let encoding = l.get("encoding").str().unwrap_or("csv");
+                    let compression = l.get("compression").str().unwrap_or_default();
+
+                    if l.get("chunks").is_null() {
+                        let width = l.get("width").u32().unwrap_or(0) as i32;
+                        json_data(l.get("data"), encoding, compression, (0, 0, width), &mut cells)?;
+                    } else {
+                        for c in l.get("chunks").array() {
+                            let x = c.get("x").f32().unwrap_or(0.) as i32;
+                            let y = c.get("y").f32().unwrap_or(0.) as i32;
+                            let width = c.get("width").u32().unwrap_or(0) as i32;
+                            json_data(c.get("data"), encoding, compression, (x, y, width), &mut cells)?;
+                        }
+                    }
+
+                    Kind::Tiles(cells)
+                }
+                "objectgroup" => Kind::Objects(
+                    l.get("objects").array().iter().map(json_object).collect(),
+                    l.get("color").str().and_then(color).unwrap_or(hex(OBJECTS)),
+                ),
+                "imagelayer" => Kind::Image(match l.get("image").str() {
+                    Some(image) if !image.is_empty() => Some(self.texture(&join(dir, image))?),
+                    _ => None,
+                }),
+                "group" => Kind::Group(self.json_layers(l.get("layers"), dir)?),
+                _ => continue,
+            };
+
+            out.push(Layer {
+                name: l.get("name").str().unwrap_or_default().to_string(),
+                visible: l.get("visible").bool().unwrap_or(true),
+                opacity: l.get("opacity").f32().unwrap_or(1.),
+                offset: vec2(
+                    l.get("offsetx").f32().unwrap_or(0.),
+                    l.get("offsety").f32().unwrap_or(0.),
+                ),
+                tint: l.get("tintcolor").str().and_then(color).unwrap_or(hex(0xFFFFFF)),
+                properties: json_properties(l.get("properties")),
+                kind,
+            });
+        }
+
+        Ok(out)
+    }
+
+    fn xml_layers(&mut self, e: &Xml, dir: &str) -> Result<Vec<Layer>, String> {
+        let mut out = Vec::new();
+
+        for l in &e.children {
+            let kind = match l.name.as_str() {
+                "layer" => {
+                    let mut cells = Vec::new();
+
+                    if let Some(data) = l.child("data") {
+                        if data.child("chunk").is_some() {
+                            for c in data.all("chunk") {
+                                let area = (
+                                    c.f32("x").unwrap_or(0.) as i32,
+                                    c.f32("y").unwrap_or(0.) as i32,
+                                    c.u32("width").unwrap_or(0) as i32,
+                                );
+                                xml_data(data, c, area, &mut cells)?;
+                            }
+                        } else {
+                            let width = l.u32("width").unwrap_or(0) as i32;
+                            xml_data(data, data, (0, 0, width), &mut cells)?;
+                        }
+                    }
+
+                    Kind::Tiles(cells)
+                }
+                "objectgroup" => Kind::Objects(
+                    l.all("object").map(xml_object).collect(),
+                    l.attr("color").and_then(color).unwrap_or(hex(OBJECTS)),
+                ),
+                "imagelayer" => Kind::Image(match l.child("image").and_then(|i| i.attr("source")) {
+                    Some(image) if !image.is_empty() => Some(self.texture(&join(dir, image))?),
+                    _ => None,
+                }),
+                "group" => Kind::Group(self.xml_layers(l, dir)?),
+                _ => continue,
+            };
+
+            out.push(Layer {
+                name: l.attr("name").unwrap_or_default().to_string(),
+                visible: l.attr("visible") != Some("0"),
+                opacity: l.f32("opacity").unwrap_or(1.),
+                offset: vec2(l.f32("offsetx").unwrap_or(0.), l.f32("offsety").unwrap_or(0.)),
+                tint: l.attr("tintcolor").and_then(color).unwrap_or(hex(0xFFFFFF)),
+                properties: xml_properties(l),
+                kind,
+            });
+        }
+
+        Ok(out)
+    }
+}
+
+fn check(orientation: Option<&str>) -> Result<(), String> {
+    match orientation.unwrap_or("orthogonal") {
+        "orthogonal" => Ok(()),
+        other => Err(format!("Unsupported map orientation: {other}")),
+    }
+}
+
+/// Клетки области (x, y, ширина) по порядку строк.
+fn cells(gids: Vec<u32>, (x, y, width): (i32, i32, i32), out: &mut Vec<(i32, i32, u32)>) {
+    let width = width.max(1);
+
+    out.extend(
+        gids.into_iter()
+            .enumerate()
+            .filter(|(_, gid)| gid & GID != 0)
+            .map(|(i, gid)| (x + i as i32 % width, y + i as i32 / width, gid)),
+    );
+}
+
+fn gids(data: &str, encoding: &str, compression: &str) -> Result<Vec<u32>, String> {
+    match encoding {
+        "csv" => data
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse().map_err(|_| format!("Bad tile gid: {s}")))
+            .collect(),
+        "base64" => Ok(parse::inflate(&parse::base64(data)?, compression)?
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()),
+        other => Err(format!("Unsupported tile encoding: {other}")),
+    }
+}
+
+fn json_data(
+    data: &Json,
+    encoding: &str,
+    compression: &str,
+    area: (i32, i32, i32),
+    out: &mut Vec<(i32, i32, u32)>,
+) -> Result<(), String> {
+    let gids = match data {
+        Json::String(s) => gids(s, encoding, compression)?,
+        data => data.array().iter().map(|g| g.u32().unwrap_or(0)).collect(),
+    };
+
+    cells(gids, area, out);
+    Ok(())
+}
+
+/// Данные слоя или чанка: кодировка берётся у `<data>`, клетки у `block`.
+fn xml_data(data: &Xml, block: &Xml, area: (i32, i32, i32), out: &mut Vec<(i32, i32, u32)>) -> Result<(), String> {
+    let gids = match data.attr("encoding") {
+        Some(encoding) => gids(&block.text, encoding, data.attr("compression").unwrap_or_default())?,
+        None => block.all("tile").map(|t| t.u32("gid").unwrap_or(0)).collect(),
+    };
+
+    cells(gids, area, out);
+    Ok(())
+}
+
+fn json_object(j: &Json) -> Object {
+    let points = |key: &str| {
+        j.get(key)
+            .array()
+            .iter()
+            .map(|p| vec2(p.get("x").f32().unwrap_or(0.), p.get("y").f32().unwrap_or(0.)))
+            .collect()
+    };
+
+    let shape = if let Some(gid) = j.get("gid").u32() {
+        Shape::Tile(gid)
+    } else if j.get("ellipse").bool() == Some(true) {
+        Shape::Ellipse
+    } else if j.get("point").bool() == Some(true) {
+        Shape::Point
+    } else if !j.get("polygon").is_null() {
+        Shape::Polygon(points("polygon"))
+    } else if !j.get("polyline").is_null() {
+        Shape::Polyline(points("polyline"))
+    } else if !j.get("text").is_null() {
+        Shape::Text(j.get("text").get("text").str().unwrap_or_default().to_string())
+    } else {
+        Shape::Rect
+    };
+
+    Object {
+        id: j.get("id").u32().unwrap_or(0),
+        name: j.get("name").str().unwrap_or_default().to_string(),
+        class: j.get("class").str().or(j.get("type").str()).unwrap_or_default().to_string(),
+        position: vec2(j.get("x").f32().unwrap_or(0.), j.get("y").f32().unwrap_or(0.)),
+        size: vec2(j.get("width").f32().unwrap_or(0.), j.get("height").f32().unwrap_or(0.)),
+        rotation: j.get("rotation").f32().unwrap_or(0.),
+        visible: j.get("visible").bool().unwrap_or(true),
+        shape,
+        properties: json_properties(j.get("properties")),
+    }
+}
+
+fn xml_object(e: &Xml) -> Object {
+    let points = |p: &Xml| {
+        p.attr("points")
+            .unwrap_or_default()
+            .split_whitespace()
+            .filter_map(|p| {
+                let (x, y) = p.split_once(',')?;
+                Some(vec2(x.parse().ok()?, y.parse().ok()?))
+            })
+            .collect()
+    };
+
+    let shape = if let Some(gid) = e.u32("gid") {
+        Shape::Tile(gid)
+    } else if e.child("ellipse").is_some() {
+        Shape::Ellipse
+    } else if e.child("point").is_some() {
+        Shape::Point
+    } else if let Some(p) = e.child("polygon") {
+        Shape::Polygon(points(p))
+    } else if let Some(p) = e.child("polyline") {
+        Shape::Polyline(points(p))
+    } else if let Some(t) = e.child("text") {
+        Shape::Text(t.text.clone())
+    } else {
+        Shape::Rect
+    };
+
+    Object {
+        id: e.u32("id").unwrap_or(0),
+        name: e.attr("name").unwrap_or_default().to_string(),
+        class: e.attr("class").or(e.attr("type")).unwrap_or_default().to_string(),
+        position: vec2(e.f32("x").unwrap_or(0.), e.f32("y").unwrap_or(0.)),
+        size: vec2(e.f32("width").unwrap_or(0.), e.f32("height").unwrap_or(0.)),
+        rotation: e.f32("rotation").unwrap_or(0.),
+        visible: e.attr("visible") != Some("0"),
+        shape,
+        properties: xml_properties(e),
+    }
+}
+
+fn property(kind: &str, value: &str) -> Property {
+    match kind {
+        "bool" => Property::Bool(value == "true"),
+        "int" => Property::Int(value.parse::<f64>().unwrap_or(0.) as i64),
+        "float" => Property::Float(value.parse().unwrap_or(0.)),
+        "color" => Property::Color(color(value).unwrap_or(Rgba::new(0., 0., 0., 0.))),
+        "object" => Property::Object(value.parse().unwrap_or(0)),
+        _ => Property::String(value.to_string()),
+    }
+}
+
+/// Свойства-классы раскладываются в ключи `имя.поле`.
+fn json_properties(properties: &Json) -> Vec<(String, Property)> {
+    let mut out = Vec::new();
+
+    for p in properties.array() {
+        let name = p.get("name").str().unwrap_or_default();
+        let kind = p.get("type").str().unwrap_or("string");
+
+        match (kind, p.get("value")) {
+            ("class", Json::Object(members)) => json_members(name, members, &mut out),
+            (kind, value) => out.push((name.to_string(), property(kind, &json_text(value)))),
+        }
+    }
+
+    out
+}
+
+/// Поля класса в JSON без типов: числа читаются как `f32`.
+fn json_members(prefix: &str, members: &[(String, Json)], out: &mut Vec<(String, Property)>) {
+    for (name, value) in members {
+        let name = format!("{prefix}.{name}");
+
+        match value {
+            Json::Object(members) => json_members(&name, members, out),
+            Json::Bool(b) => out.push((name, Property::Bool(*b))),
+            Json::Number(n) => out.push((name, Property::Float(*n as f32))),
+            value => out.push((name, Property::String(json_text(value)))),
+        }
+    }
+}
+
+fn json_text(value: &Json) -> String {
+    match value {
+        Json::String(s) => s.clone(),
+        Json::Bool(b) => b.to_string(),
+        Json::Number(n) => n.to_string(),
+        _ => String::new(),
+    }
+}
+
+fn xml_properties(e: &Xml) -> Vec<(String, Property)> {
+    let mut out = Vec::new();
+    if let Some(properties) = e.child("properties") {
+        xml_members("", properties, &mut out);
+    }
+    out
+}
+
+fn xml_members(prefix: &str, properties: &Xml, out: &mut Vec<(String, Property)>) {
+    for p in properties.all("property") {
+        let name = format!("{prefix}{}", p.attr("name").unwrap_or_default());
+
+        match p.child("properties") {
+            Some(members) => xml_members(&format!("{name}."), members, out),
+            None => {
+                // Многострочная строка хранится текстом элемента
+                let value = p.attr("value").unwrap_or(&p.text);
+                out.push((name, property(p.attr("type").unwrap_or("string"), value)));
+            }
+        }
+    }
+}
+
+/// Цвет `#AARRGGBB` или `#RRGGBB`.
+fn color(s: &str) -> Option<Rgba> {
+    let s = s.trim_start_matches('#');
+    let v = u32::from_str_radix(s, 16).ok()?;
+
+    let a = match s.len() {
+        8 => (v >> 24) as u8,
+        6 => 255,
+        _ => return None,
+    };
+
+    Some(rgba((v >> 16) as u8, (v >> 8) as u8, v as u8, a as f32 / 255.))
+}
+
+#[inline(always)]
+fn hex(v: u32) -> Rgba {
+    rgba((v >> 16) as u8, (v >> 8) as u8, v as u8, 1.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::FULL_UV;
+
+    fn tileset(first: u32) -> Tileset {
+        Tileset {
+            first,
+            name: String::new(),
+            texture: Texture::new(0, 64., 64., FULL_UV),
+            tile: vec2(16., 16.),
+            margin: 0.,
+            spacing: 0.,
+            animations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn gid_flags() {
+        let sets = [tileset(1), tileset(17)];
+
+        assert_eq!(find(&sets, 0), None);
+        assert_eq!(find(&sets, FLIP_X), None);
+        assert_eq!(find(&sets, 5), Some((0, Tile::new(4))));
+        assert_eq!(find(&sets, 17), Some((1, Tile::new(0))));
+        assert_eq!(find(&sets, 20 | FLIP_X), Some((1, Tile::new(3).flip(true, false))));
+        assert_eq!(
+            find(&sets, 2 | FLIP_Y | FLIP_D),
+            Some((0, Tile::new(1).flip(false, true).diagonal(true)))
+        );
+    }
+
+    #[test]
+    fn tile_data() {
+        let expected = vec![1, 2 | FLIP_X, 0, 3 | FLIP_Y | FLIP_D];
+
+        assert_eq!(gids("AQAAAAIAAIAAAAAAAwAAYA==", "base64", "").unwrap(), expected);
+        assert_eq!(gids("eJxjZGBgYGJgaABSDMwMDAkABSQA5w==", "base64", "zlib").unwrap(), expected);
+        assert_eq!(gids("1,\n2147483650, 0,\n1610612739\n", "csv", "").unwrap(), expected);
+        assert!(gids("AQAAAA==", "base64", "zstd").is_err());
+        assert!(gids("1,x", "csv", "").is_err());
+
+        let mut out = Vec::new();
+        cells(expected, (2, 3, 2), &mut out);
+        assert_eq!(out, [(2, 3, 1), (3, 3, 2 | FLIP_X), (3, 4, 3 | FLIP_Y | FLIP_D)]);
+    }
+}
@@ -0,0 +1,347 @@
+use crate::render::Texture;
+
+use glam::{vec2, Vec2};
+use std::collections::HashMap;
+
+/// Сторона чанка в клетках.
+const CHUNK: i32 = 16;
+
+/// Клетка карты: номер тайла в наборе (слева направо, сверху вниз) и отражения.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Tile {
+    pub id: u32,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// Отражение по диагонали (поворот вместе с `flip_x` / `flip_y`).
+    pub flip_d: bool,
+}
+
+impl Tile {
+    pub fn new(id: u32) -> Self {
+        Self {
+            id,
+            flip_x: false,
+            flip_y: false,
+            flip_d: false,
+        }
+    }
+
+    pub fn flip(mut self, x: bool, y: bool) -> Self {
+        self.flip_x = x;
+        self.flip_y = y;
+        self
+    }
+
+    pub fn diagonal(mut self, d: bool) -> Self {
+        self.flip_d = d;
+        self
+    }
+}
+
+#[derive(Clone, PartialEq)]
+struct Chunk {
+    cells: Vec<Option<Tile>>,
+    /// Готовая геометрия: позиция и uv в долях текстуры, по 4 вершины на тайл.
+    mesh: Vec<(Vec2, Vec2)>,
+    /// Занятые клетки чанка: наименьшая и наибольшая.
+    bounds: Option<((i32, i32), (i32, i32))>,
+    dirty: bool,
+    animated: bool,
+}
+
+impl Chunk {
+    fn new() -> Self {
+        Self {
+            cells: vec![None; (CHUNK * CHUNK) as usize],
+            mesh: Vec::new(),
+            bounds: None,
+            dirty: true,
+            animated: false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+struct Animation {
+    frames: Vec<u32>,
+    fps: f32,
+}
+
+/// Сетка тайлов из одной текстуры-набора. Клетка (0, 0) левым верхним углом
+/// в центре узла. Геометрия строится чанками 16x16 и пересобирается только
+/// у изменённых, рисуются чанки в кадре камеры.
+#[derive(Clone, PartialEq)]
+pub struct TileMap {
+    pub(crate) texture: Texture,
+    tile: Vec2,
+    /// Шаг сетки, обычно равен размеру тайла.
+    grid: Vec2,
+    margin: f32,
+    spacing: f32,
+    chunks: HashMap<(i32, i32), Chunk>,
+    animations: HashMap<u32, Animation>,
+    time: f32,
+    /// Половина занятой части карты от центра узла по осям.
+    pub(crate) extent: Vec2,
+    /// Чанки в кадре, по порядку отрисовки.
+    pub(crate) visible: Vec<(i32, i32)>,
+    /// Нужно пересобрать геометрию узла.
+    pub(crate) dirty: bool,
+    /// Убрали клетку с края, `extent` считается заново.
+    shrunk: bool,
+}
+
+impl TileMap {
+    pub fn new(texture: &Texture, tile_width: f32, tile_height: f32) -> Self {
+        Self {
+            texture: *texture,
+            tile: vec2(tile_width, tile_height),
+            grid: vec2(tile_width, tile_height),
+            margin: 0.,
+            spacing: 0.,
+            chunks: HashMap::new(),
+            animations: HashMap::new(),
+            time: 0.,
+            extent: Vec2::ZERO,
+            visible: Vec::new(),
+            dirty: true,
+            shrunk: false,
+        }
+    }
+
+    /// Отступ от края набора и промежуток между тайлами в пикселях.
+    pub fn spacing(mut self, margin: f32, spacing: f32) -> Self {
+        self.margin = margin;
+        self.spacing = spacing;
+        self
+    }
+
+    /// Шаг сетки, если он отличается от размера тайла. Тайл крепится
+    /// к клетке левым нижним углом, как в Tiled.
+    pub fn grid(mut self, width: f32, height: f32) -> Self {
+        self.grid = vec2(width, height);
+        self
+    }
+
+    /// Тайл `tile` показывает кадры `frames` по кругу.
+    pub fn animation(mut self, tile: u32, frames: &[u32], fps: f32) -> Self {
+        if frames.is_empty() {
+            return self;
+        }
+
+        self.animations.insert(
+            tile,
+            Animation {
+                frames: frames.to_vec(),
+                fps: fps.max(0.001),
+            },
+        );
+        self
+    }
+
+    pub fn tile_size(&self) -> Vec2 {
+        self.tile
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<Tile> {
+        let (key, i) = cell(x, y);
+        self.chunks.get(&key)?.cells[i]
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, tile: impl Into<Option<Tile>>) {
+        let tile = tile.into();
+        let (key, i) = cell(x, y);
+
+        let chunk = match (self.chunks.get_mut(&key), tile) {
+            (Some(chunk), _) => chunk,
+            (None, None) => return,
+            (None, Some(_)) => self.chunks.entry(key).or_insert_with(Chunk::new),
+        };
+
+        if chunk.cells[i] != tile {
+            chunk.cells[i] = tile;
+            chunk.dirty = true;
+
+            match tile {
+                Some(_) => {
+                    chunk.bounds = Some(match chunk.bounds {
+                        Some(b) => join(b, ((x, y), (x, y))),
+                        None => ((x, y), (x, y)),
+                    });
+                    let extent = self.extent.max(reach(self.grid, self.tile, ((x, y), (x, y))));
+                    self.dirty |= extent != self.extent;
+                    self.extent = extent;
+                }
+                // Клетка с края: границы чанка сужаются, карты - при следующем advance
+                None => {
+                    if chunk.bounds.is_some_and(|(lo, hi)| x == lo.0 || y == lo.1 || x == hi.0 || y == hi.1) {
+                        chunk.bounds = bounds(key, &chunk.cells);
+                        self.shrunk = true;
+                    }
+                }
+            }
+
+            if chunk.bounds.is_none() {
+                self.chunks.remove(&key);
+                self.dirty |= self.visible.contains(&key);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.extent = Vec2::ZERO;
+        self.dirty = true;
+    }
+
+    /// Клетка под точкой в локальных координатах узла.
+    pub fn cell(&self, local: Vec2) -> (i32, i32) {
+        let c = (local / self.grid).floor();
+        (c.x as i32, c.y as i32)
+    }
+
+    /// Занятые клетки: наименьшая и наибольшая.
+    pub fn used(&self) -> Option<((i32, i32), (i32, i32))> {
+        self.chunks.values().filter_map(|c| c.bounds).reduce(join)
+    }
+
+    /// Анимации тайлов и пересборка изменённых чанков.
+    pub(crate) fn advance(&mut self, delta: f32) {
+        let before = self.time;
+        self.time += delta;
+
+        let tick = self
+            .animations
+            .values()
+            .any(|a| (before * a.fps) as u64 != (self.time * a.fps) as u64);
+
+        let keys: Vec<_> = self.chunks.keys().copied().collect();
+        for key in keys {
+            let chunk = &self.chunks[&key];
+            if chunk.dirty || (tick && chunk.animated) {
+                self.rebuild(key);
+                self.dirty |= self.visible.contains(&key);
+            }
+        }
+
+        // Границы чанков уже известны, клетки не обходятся
+        if self.shrunk {
+            self.shrunk = false;
+            let extent = self.used().map_or(Vec2::ZERO, |used| reach(self.grid, self.tile, used));
+            self.dirty |= extent != self.extent;
+            self.extent = extent;
+        }
+    }
+
+    /// Оставляет чанки, пересекающие область `min`..`max` в локальных координатах.
+    pub(crate) fn cull(&mut self, min: Vec2, max: Vec2) {
+        // Тайлы крупнее клетки вылезают из чанка вправо и вверх
+        let over = (self.tile - self.grid).max(Vec2::ZERO);
+        let size = self.grid * CHUNK as f32;
+        let lo = ((min - vec2(over.x, 0.)) / size).floor();
+        let hi = ((max + vec2(0., over.y)) / size).floor();
+
+        let mut visible: Vec<(i32, i32)> = self
+            .chunks
+            .keys()
+            .filter(|(x, y)| {
+                (lo.x as i32..=hi.x as i32).contains(x) && (lo.y as i32..=hi.y as i32).contains(y)
+            })
+            .copied()
+            .collect();
+        visible.sort_by_key(|&(x, y)| (y, x));
+
+        if visible != self.visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    /// Геометрия чанка в порядке отрисовки.
+    pub(crate) fn mesh(&self, key: (i32, i32)) -> &[(Vec2, Vec2)] {
+        self.chunks.get(&key).map_or(&[], |c| &c.mesh)
+    }
+
+    fn rebuild(&mut self, key: (i32, i32)) {
+        let mut chunk = self.chunks.remove(&key).unwrap();
+        chunk.mesh.clear();
+        chunk.animated = false;
+
+        let columns = ((self.texture.width - self.margin * 2. + self.spacing)
+            / (self.tile.x + self.spacing))
+            .floor()
+            .max(1.) as u32;
+
+        for (i, tile) in chunk.cells.iter().enumerate() {
+            let Some(tile) = tile else { continue };
+
+            let id = match self.animations.get(&tile.id) {
+                Some(a) => {
+                    chunk.animated = true;
+                    a.frames[(self.time * a.fps) as usize % a.frames.len()]
+                }
+                None => tile.id,
+            };
+
+            let x = (key.0 * CHUNK + i as i32 % CHUNK) as f32;
+            let y = (key.1 * CHUNK + i as i32 / CHUNK) as f32;
+            let pos = vec2(x, y) * self.grid + vec2(0., self.grid.y - self.tile.y);
+
+            let source = vec2((id % columns) as f32, (id / columns) as f32) * (self.tile + self.spacing)
+                + self.margin;
+            let size = vec2(self.texture.width, self.texture.height);
+
+            for corner in [vec2(0., 0.), vec2(1., 0.), vec2(1., 1.), vec2(0., 1.)] {
+                // Угол источника: отражения в обратном порядке, диагональ последней
+                let mut c = corner;
+                if tile.flip_x {
+                    c.x = 1. - c.x;
+                }
+                if tile.flip_y {
+                    c.y = 1. - c.y;
+                }
+                if tile.flip_d {
+                    c = vec2(c.y, c.x);
+                }
+
+                chunk.mesh.push((pos + corner * self.tile, (source + c * self.tile) / size));
+            }
+        }
+
+        chunk.dirty = false;
+        self.chunks.insert(key, chunk);
+    }
+}
+
+/// Область, накрывающая обе.
+fn join((lo, hi): ((i32, i32), (i32, i32)), (l, h): ((i32, i32), (i32, i32))) -> ((i32, i32), (i32, i32)) {
+    ((lo.0.min(l.0), lo.1.min(l.1)), (hi.0.max(h.0), hi.1.max(h.1)))
+}
+
+/// Занятые клетки чанка `key` по его клеткам.
+fn bounds(key: (i32, i32), cells: &[Option<Tile>]) -> Option<((i32, i32), (i32, i32))> {
+    cells
+        .iter()
+        .enumerate()
+        .filter(|(_, tile)| tile.is_some())
+        .map(|(i, _)| {
+            let p = (key.0 * CHUNK + i as i32 % CHUNK, key.1 * CHUNK + i as i32 / CHUNK);
+            (p, p)
+        })
+        .reduce(join)
+}
+
+/// Половина области от центра узла, которую накрывают тайлы клеток `lo`..`hi`.
+fn reach(grid: Vec2, tile: Vec2, (lo, hi): ((i32, i32), (i32, i32))) -> Vec2 {
+    let lo = vec2(lo.0 as f32, lo.1 as f32 + 1.) * grid - vec2(0., tile.y);
+    let hi = vec2(hi.0 as f32, hi.1 as f32 + 1.) * grid + vec2(tile.x, 0.);
+    lo.abs().max(hi.abs())
+}
+
+/// Чанк и номер клетки в нём.
+#[inline(always)]
+fn cell(x: i32, y: i32) -> ((i32, i32), usize) {
+    let key = (x.div_euclid(CHUNK), y.div_euclid(CHUNK));
+    let i = y.rem_euclid(CHUNK) * CHUNK + x.rem_euclid(CHUNK);
+    (key, i as usize)
+}
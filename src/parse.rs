@@ -0,0 +1,636 @@
+//! Небольшие разборщики JSON и XML для импорта карт и анимаций.
+
+use crate::data::load_file;
+
+/// Наибольшая вложенность массивов, объектов и элементов.
+/// Глубже файл считается испорченным, а не разбирается до переполнения стека.
+const DEPTH: usize = 256;
+
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Поля в порядке файла.
+    Object(Vec<(String, Json)>),
+}
+
+static NULL: Json = Json::Null;
+
+impl Json {
+    pub(crate) fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map_or(&NULL, |(_, v)| v),
+            _ => &NULL,
+        }
+    }
+
+    pub(crate) fn is_null(&self) -> bool {
+        *self == Json::Null
+    }
+
+    pub(crate) fn f32(&self) -> Option<f32> {
+        match self {
+            Json::Number(n) => Some(*n as f32),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn u32(&self) -> Option<u32> {
+        match self {
+            Json::Number(n) => Some(*n as u32),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn array(&self) -> &[Json] {
+        match self {
+            Json::Array(a) => a,
+            _ => &[],
+        }
+    }
+}
+
+pub(crate) fn json(text: &str) -> Result<Json, String> {
+    let mut p = Parser {
+        s: text.as_bytes(),
+        i: 0,
+        depth: 0,
+    };
+    let value = p.value()?;
+    p.space();
+
+    if p.i != p.s.len() {
+        return Err(p.error("trailing characters"));
+    }
+
+    Ok(value)
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    i: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, what: &str) -> String {
+        format!("JSON: {what} at byte {}", self.i)
+    }
+
+    fn space(&mut self) {
+        while self.i < self.s.len() && self.s[self.i].is_ascii_whitespace() {
+            self.i += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.space();
+        self.s.get(self.i).copied()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        if self.peek() == Some(c) {
+            self.i += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", c as char)))
+        }
+    }
+
+    fn word(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        if self.s[self.i..].starts_with(word.as_bytes()) {
+            self.i += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("unknown literal"))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        if self.depth == DEPTH {
+            return Err(self.error("nested too deep"));
+        }
+
+        self.depth += 1;
+        let value = self.item();
+        self.depth -= 1;
+        value
+    }
+
+    fn item(&mut self) -> Result<Json, String> {
+        match self.peek() {
+            Some(b'{') => {
+                self.i += 1;
+                let mut map = Vec::new();
+
+                if self.peek() == Some(b'}') {
+                    self.i += 1;
+                    return Ok(Json::Object(map));
+                }
+
+                loop {
+                    self.space();
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    map.push((key, self.value()?));
+
+                    match self.peek() {
+                        Some(b',') => self.i += 1,
+                        Some(b'}') => {
+                            self.i += 1;
+                            return Ok(Json::Object(map));
+                        }
+                        _ => return Err(self.error("expected ',' or '}'")),
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.i += 1;
+                let mut list = Vec::new();
+
+                if self.peek() == Some(b']') {
+                    self.i += 1;
+                    return Ok(Json::Array(list));
+                }
+
+                loop {
+                    list.push(self.value()?);
+
+                    match self.peek() {
+                        Some(b',') => self.i += 1,
+                        Some(b']') => {
+                            self.i += 1;
+                            return Ok(Json::Array(list));
+                        }
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            }
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b't') => self.word("true", Json::Bool(true)),
+            Some(b'f') => self.word("false", Json::Bool(false)),
+            Some(b'n') => self.word("null", Json::Null),
+            Some(_) => {
+                let start = self.i;
+                while self.i < self.s.len() && matches!(self.s[self.i], b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') {
+                    self.i += 1;
+                }
+
+                std::str::from_utf8(&self.s[start..self.i])
+                    .ok()
+                    .and_then(|n| n.parse().ok())
+                    .map(Json::Number)
+                    .ok_or_else(|| self.error("bad number"))
+            }
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.s.get(self.i) != Some(&b'"') {
+            return Err(self.error("expected string"));
+        }
+        self.i += 1;
+
+        let mut out = Vec::new();
+
+        loop {
+            let c = *self.s.get(self.i).ok_or_else(|| self.error("unterminated string"))?;
+            self.i += 1;
+
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let e = *self.s.get(self.i).ok_or_else(|| self.error("bad escape"))?;
+                    self.i += 1;
+
+                    let c = match e {
+                        b'n' => '\n',
+                        b't' => '\t',
+                        b'r' => '\r',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'u' => {
+                            let mut code = self.hex()?;
+
+                            // Суррогатная пара
+                            if (0xD800..0xDC00).contains(&code) && self.s[self.i..].starts_with(b"\\u") {
+                                self.i += 2;
+                                let low = self.hex()?;
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+
+                            char::from_u32(code).unwrap_or('\u{FFFD}')
+                        }
+                        c => c as char,
+                    };
+
+                    let mut buf = [0; 4];
+                    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                c => out.push(c),
+            }
+        }
+
+        String::from_utf8(out).map_err(|_| self.error("invalid UTF-8"))
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let code = self
+            .s
+            .get(self.i..self.i + 4)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u32::from_str_radix(h, 16).ok())
+            .ok_or_else(|| self.error("bad \\u escape"))?;
+        self.i += 4;
+        Ok(code)
+    }
+}
+
+/// Элемент XML: имя, атрибуты, дети и текст внутри.
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) struct Xml {
+    pub(crate) name: String,
+    pub(crate) attributes: Vec<(String, String)>,
+    pub(crate) children: Vec<Xml>,
+    pub(crate) text: String,
+}
+
+impl Xml {
+    pub(crate) fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub(crate) fn f32(&self, name: &str) -> Option<f32> {
+        self.attr(name)?.trim().parse().ok()
+    }
+
+    pub(crate) fn u32(&self, name: &str) -> Option<u32> {
+        self.attr(name)?.trim().parse().ok()
+    }
+
+    pub(crate) fn child(&self, name: &str) -> Option<&Xml> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    pub(crate) fn all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Xml> + 'a {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+}
+
+/// Разбирает документ и возвращает корневой элемент.
+/// Пролог, комментарии, CDATA и инструкции обработки пропускаются или читаются как текст.
+pub(crate) fn xml(text: &str) -> Result<Xml, String> {
+    let mut stack: Vec<Xml> = Vec::new();
+    let mut root: Option<Xml> = None;
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        if let Some(top) = stack.last_mut() {
+            top.text.push_str(&unescape(&rest[..start]));
+        }
+        rest = &rest[start..];
+
+        if let Some(body) = rest.strip_prefix("<!--") {
+            let end = body.find("-->").ok_or("XML: unterminated comment")?;
+            rest = &body[end + 3..];
+        } else if let Some(body) = rest.strip_prefix("<![CDATA[") {
+            let end = body.find("]]>").ok_or("XML: unterminated CDATA")?;
+            if let Some(top) = stack.last_mut() {
+                top.text.push_str(&body[..end]);
+            }
+            rest = &body[end + 3..];
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            let end = rest.find('>').ok_or("XML: unterminated declaration")?;
+            rest = &rest[end + 1..];
+        } else if let Some(body) = rest.strip_prefix("</") {
+            let end = body.find('>').ok_or("XML: unterminated closing tag")?;
+            let name = body[..end].trim();
+            rest = &body[end + 1..];
+
+            let element = stack.pop().ok_or("XML: unexpected closing tag")?;
+            if element.name != name {
+                return Err(format!("XML: <{}> closed by </{}>", element.name, name));
+            }
+            close(&mut stack, &mut root, element);
+        } else {
+            let end = tag_end(rest).ok_or("XML: unterminated tag")?;
+            let tag = &rest[1..end];
+            rest = &rest[end + 1..];
+
+            let (tag, empty) = match tag.strip_suffix('/') {
+                Some(tag) => (tag, true),
+                None => (tag, false),
+            };
+
+            let element = element(tag)?;
+            if empty {
+                close(&mut stack, &mut root, element);
+            } else if stack.len() == DEPTH {
+                return Err(format!("XML: <{}> nested too deep", element.name));
+            } else {
+                stack.push(element);
+            }
+        }
+    }
+
+    if !stack.is_empty() {
+        return Err("XML: unclosed element".to_string());
+    }
+
+    root.ok_or_else(|| "XML: no root element".to_string())
+}
+
+fn close(stack: &mut [Xml], root: &mut Option<Xml>, element: Xml) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(element),
+        None => *root = Some(element),
+    }
+}
+
+/// Конец тега с учётом `>` внутри кавычек атрибутов.
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn element(tag: &str) -> Result<Xml, String> {
+    let tag = tag.trim();
+    let split = tag.find(char::is_whitespace).unwrap_or(tag.len());
+
+    let mut element = Xml {
+        name: tag[..split].to_string(),
+        attributes: Vec::new(),
+        children: Vec::new(),
+        text: String::new(),
+    };
+
+    let mut rest = tag[split..].trim_start();
+    while !rest.is_empty() {
+        let eq = rest.find('=').ok_or_else(|| format!("XML: bad attribute in <{}>", element.name))?;
+        let name = rest[..eq].trim().to_string();
+        let value = rest[eq + 1..].trim_start();
+
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'');
+        let quote = quote.ok_or_else(|| format!("XML: unquoted attribute {name}"))?;
+        let end = value[1..].find(quote).ok_or("XML: unterminated attribute")?;
+
+        element.attributes.push((name, unescape(&value[1..end + 1])));
+        rest = value[end + 2..].trim_start();
+    }
+
+    Ok(element)
+}
+
+fn unescape(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        let Some(end) = rest.find(';') else { break };
+        let entity = &rest[1..end];
+
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32),
+        };
+
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+pub(crate) fn base64(text: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut count = 0;
+
+    for c in text.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            c if c.is_ascii_whitespace() => continue,
+            _ => return Err("Base64: bad character".to_string()),
+        };
+
+        bits = bits << 6 | v as u32;
+        count += 6;
+
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+
+    Ok(out)
+}
+
+/// Распаковка zlib или gzip.
+pub(crate) fn inflate(data: &[u8], compression: &str) -> Result<Vec<u8>, String> {
+    use miniz_oxide::inflate::{decompress_to_vec, decompress_to_vec_zlib};
+
+    match compression {
+        "" => Ok(data.to_vec()),
+        "zlib" => decompress_to_vec_zlib(data).map_err(|e| format!("zlib: {e:?}")),
+        "gzip" => {
+            // Заголовок gzip: 10 байт и необязательные поля по флагам
+            let flags = *data.get(3).ok_or("gzip: short header")?;
+            let mut i = 10;
+
+            if flags & 4 != 0 {
+                let len = data.get(i..i + 2).ok_or("gzip: short header")?;
+                i += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+            }
+            for flag in [8, 16] {
+                if flags & flag != 0 {
+                    let rest = data.get(i..).ok_or("gzip: short header")?;
+                    i += rest.iter().position(|&b| b == 0).ok_or("gzip: bad header")? + 1;
+                }
+            }
+            if flags & 2 != 0 {
+                i += 2;
+            }
+
+            decompress_to_vec(data.get(i..).ok_or("gzip: short data")?).map_err(|e| format!("gzip: {e:?}"))
+        }
+        other => Err(format!("Unsupported compression: {other}")),
+    }
+}
+
+pub(crate) fn read(path: &str) -> Result<String, String> {
+    let bytes = load_file(path).map_err(|e| format!("{path}: {e}"))?;
+    String::from_utf8(bytes).map_err(|_| format!("{path}: not UTF-8"))
+}
+
+#[inline(always)]
+pub(crate) fn dir(path: &str) -> &str {
+    path.rfind(['/', '\\']).map_or("", |i| &path[..i])
+}
+
+/// Путь относительно папки файла, который на него ссылается.
+pub(crate) fn join(dir: &str, path: &str) -> String {
+    if dir.is_empty() || path.starts_with('/') || path.contains(':') {
+        return path.to_string();
+    }
+
+    let mut out: Vec<&str> = dir.split(['/', '\\']).collect();
+
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." if matches!(out.last(), Some(p) if !p.is_empty() && *p != "..") => {
+                out.pop();
+            }
+            part => out.push(part),
+        }
+    }
+
+    out.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_escapes() {
+        let j = json(r#"{"s": "a\"b\\c\/d\n\té", "pair": "😀", "lone": "\ud83d!"}"#).unwrap();
+
+        assert_eq!(j.get("s").str(), Some("a\"b\\c/d\n\té"));
+        assert_eq!(j.get("pair").str(), Some("😀"));
+        assert_eq!(j.get("lone").str(), Some("\u{FFFD}!"));
+    }
+
+    #[test]
+    fn json_values() {
+        let j = json(r#" {"n": [1, -2.5, 3e2], "b": true, "z": null, "o": {"k": "v"}} "#).unwrap();
+
+        let n: Vec<f32> = j.get("n").array().iter().filter_map(Json::f32).collect();
+        assert_eq!(n, [1., -2.5, 300.]);
+        assert_eq!(j.get("b").bool(), Some(true));
+        assert!(j.get("z").is_null());
+        assert!(j.get("missing").is_null());
+        assert_eq!(j.get("o").get("k").str(), Some("v"));
+
+        assert!(json(r#"{"a": 1"#).is_err());
+        assert!(json(r#""\u12""#).is_err());
+        assert!(json("[1] 2").is_err());
+    }
+
+    #[test]
+    fn xml_cdata_and_entities() {
+        let x = xml(concat!(
+            r#"<?xml version="1.0"?><!-- map --><map name="a &amp; b" q='&quot;&#65;&#x42;'>"#,
+            r#"<data><![CDATA[1,<2>,&amp;]]></data><tile gid="3"/><p v="x>y"/></map>"#,
+        ))
+        .unwrap();
+
+        assert_eq!(x.name, "map");
+        assert_eq!(x.attr("name"), Some("a & b"));
+        assert_eq!(x.attr("q"), Some("\"AB"));
+        assert_eq!(x.child("data").unwrap().text, "1,<2>,&amp;");
+        assert_eq!(x.child("tile").unwrap().u32("gid"), Some(3));
+        assert_eq!(x.child("p").unwrap().attr("v"), Some("x>y"));
+
+        assert_eq!(unescape("&lt;&unknown;&gt"), "<&unknown;&gt");
+        assert!(xml("<a><b></a>").is_err());
+        assert!(xml("<a><![CDATA[x</a>").is_err());
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let deep = |open: &str, close: &str, n: usize| open.repeat(n) + &close.repeat(n);
+
+        assert!(json(&deep("[", "]", DEPTH)).is_ok());
+        assert!(json(&deep("[", "]", DEPTH + 1)).is_err());
+        // Без предела такой файл переполняет стек
+        assert!(json(&deep("[", "]", 100_000)).is_err());
+
+        assert!(xml(&deep("<a>", "</a>", DEPTH)).is_ok());
+        assert!(xml(&deep("<a>", "</a>", DEPTH + 1)).is_err());
+        assert!(xml(&deep("<a>", "</a>", 100_000)).is_err());
+    }
+
+    #[test]
+    fn base64_decoding() {
+        assert_eq!(base64("dGlsZXM=").unwrap(), b"tiles");
+        assert_eq!(base64(" dGls\nZXM ").unwrap(), b"tiles");
+        assert!(base64("dG*s").is_err());
+    }
+
+    #[test]
+    fn gzip_header() {
+        let body = [43, 201, 204, 73, 45, 6, 0, 187, 132, 21, 28, 5, 0, 0, 0];
+        let mut named = vec![31, 139, 8, 8, 0, 0, 0, 0, 2, 3, b'a', 0];
+        named.extend(body);
+
+        assert_eq!(inflate(&named, "gzip").unwrap(), b"tiles");
+
+        // Обрезанный заголовок - ошибка, а не паника
+        assert!(inflate(&[31, 139, 8], "gzip").is_err());
+        assert!(inflate(&[31, 139, 8, 4, 0, 0, 0, 0, 2, 3, 9], "gzip").is_err());
+        assert!(inflate(&[31, 139, 8, 4, 0, 0, 0, 0, 2, 3, 255, 255], "gzip").is_err());
+        assert!(inflate(&[31, 139, 8, 8, 0, 0, 0, 0, 2, 3, b'a'], "gzip").is_err());
+        assert!(inflate(&[31, 139, 8, 2, 0, 0, 0, 0, 2, 3], "gzip").is_err());
+    }
+}
@@ -1,9 +1,9 @@
 pub use crate::data::*;
 pub use crate::info::*;
-pub use crate::object::{d2::*, Keep, Key, Touch};
+pub use crate::object::{aseprite::*, d2::*, gesture::*, particles::*, prefab::*, sprite::*, tiled::*, tilemap::*, Anchor, Axis, Drag, Hit, Input, Keep, Key, Occluder, Pointer, Stretch, Touch};
 //pub use crate::physic::*;
 pub use crate::engine::*;
-pub use crate::render::{*, d2::*};
+pub use crate::render::{*, atlas::atlas, d2::*, light::{Light, Shadow}, material::*, shape::{Cap, Gradient, Join, Line, Path, Stroke}};
 
 pub use Keep::*;
 pub use Key::*;
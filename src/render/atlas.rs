@@ -0,0 +1,274 @@
+use super::{add_texture_buffer, alpha_update, load_image, read_texture, Texture, TEXUTRES_BUFFER, TEXUTRES_ID};
+
+use std::{cmp::Reverse, collections::HashMap};
+
+/// Размер страницы атласа в пикселях.
+pub(crate) const PAGE: u32 = 2048;
+/// Текстуры крупнее этого размера не упаковываются.
+const MAX: u32 = 512;
+/// Отступ вокруг каждой текстуры, заполненный краевыми пикселями.
+const PADDING: u32 = 1;
+
+pub(crate) static mut ATLAS: bool = true;
+/// Частичные обновления текстур: (id, x, y, ширина, высота, rgba).
+pub(crate) static mut TEXUTRES_PART: Vec<(usize, u32, u32, u32, u32, Vec<u8>)> = Vec::new();
+
+static mut PAGES: Vec<Page> = Vec::new();
+/// Отдельные копии областей атласа: (страница, uv области, id копии).
+static mut UNPACKED: Vec<(usize, [f32; 4], usize)> = Vec::new();
+
+struct Shelf {
+    x: u32,
+    y: u32,
+    height: u32,
+}
+
+/// Страница атласа. Пиксели живут только на GPU: копия на CPU
+/// читается оттуда, когда она нужна (`unpack`).
+pub(crate) struct Page {
+    id: usize,
+    shelves: Vec<Shelf>,
+    bottom: u32,
+    /// Занятые места: левый верхний угол -> размер с отступом.
+    slots: HashMap<(u32, u32), (u32, u32)>,
+    /// Освобождённые места, занимаются раньше новых.
+    free: Vec<((u32, u32), (u32, u32))>,
+}
+
+impl Page {
+    fn new() -> Self {
+        unsafe {
+            // Пустой буфер: бэкенд создаёт текстуру без данных
+            TEXUTRES_BUFFER.push((Vec::new(), PAGE as u16, PAGE as u16));
+            TEXUTRES_ID += 1;
+
+            Self {
+                id: TEXUTRES_ID - 1,
+                shelves: Vec::new(),
+                bottom: 0,
+                slots: HashMap::new(),
+                free: Vec::new(),
+            }
+        }
+    }
+
+    fn alloc(&mut self, w: u32, h: u32) -> Option<(u32, u32)> {
+        // Самое маленькое подходящее свободное место
+        let free = self
+            .free
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, (fw, fh)))| w <= *fw && h <= *fh)
+            .min_by_key(|(_, (_, (fw, fh)))| fw * fh)
+            .map(|(i, _)| i);
+
+        if let Some(i) = free {
+            let (pos, size) = self.free.swap_remove(i);
+            self.slots.insert(pos, size);
+            return Some(pos);
+        }
+
+        for shelf in &mut self.shelves {
+            if h <= shelf.height && shelf.x + w <= PAGE {
+                let x = shelf.x;
+                shelf.x += w;
+                self.slots.insert((x, shelf.y), (w, h));
+                return Some((x, shelf.y));
+            }
+        }
+
+        if w > PAGE || self.bottom + h > PAGE {
+            return None;
+        }
+
+        let y = self.bottom;
+        self.shelves.push(Shelf { x: w, y, height: h });
+        self.bottom += h;
+        self.slots.insert((0, y), (w, h));
+        Some((0, y))
+    }
+
+    /// Записывает картинку в место `(x, y)` размером `slot`, остаток места очищается.
+    fn write(&mut self, x: u32, y: u32, slot: (u32, u32), rgba: &[u8], w: u32, h: u32) -> Texture {
+        let (sw, sh) = slot;
+        let mut block = vec![0; (sw * sh * 4) as usize];
+
+        for py in 0..(h + PADDING * 2).min(sh) {
+            let sy = py.saturating_sub(PADDING).min(h - 1);
+            for px in 0..(w + PADDING * 2).min(sw) {
+                let sx = px.saturating_sub(PADDING).min(w - 1);
+                let src = ((sy * w + sx) * 4) as usize;
+                let dst = ((py * sw + px) * 4) as usize;
+                block[dst..dst + 4].copy_from_slice(&rgba[src..src + 4]);
+            }
+        }
+
+        alpha_update(self.id);
+
+        let page = PAGE as f32;
+        let slot = [x as f32 / page, y as f32 / page, (x + sw) as f32 / page, (y + sh) as f32 / page];
+
+        unsafe {
+            TEXUTRES_PART.push((self.id, x, y, sw, sh, block));
+
+            // Копии областей этого места больше не совпадают с атласом
+            UNPACKED.retain(|(id, uv, _)| {
+                *id != self.id || uv[2] <= slot[0] || uv[0] >= slot[2] || uv[3] <= slot[1] || uv[1] >= slot[3]
+            });
+        }
+
+        let (x, y) = ((x + PADDING) as f32, (y + PADDING) as f32);
+
+        Texture::new(
+            self.id,
+            w as f32,
+            h as f32,
+            [x / page, y / page, (x + w as f32) / page, (y + h as f32) / page],
+        )
+    }
+}
+
+/// Упаковывает картинку в атлас. `None`, если она слишком большая
+/// или атлас выключен (и упаковка не принудительная).
+pub(crate) fn pack(rgba: &[u8], w: u32, h: u32, force: bool) -> Option<Texture> {
+    if !(force || unsafe { ATLAS }) || w == 0 || h == 0 || w > MAX || h > MAX {
+        return None;
+    }
+
+    let size = (w + PADDING * 2, h + PADDING * 2);
+
+    unsafe {
+        for page in PAGES.iter_mut() {
+            if let Some((x, y)) = page.alloc(size.0, size.1) {
+                let slot = page.slots[&(x, y)];
+                return Some(page.write(x, y, slot, rgba, w, h));
+            }
+        }
+
+        let mut page = Page::new();
+        let (x, y) = page.alloc(size.0, size.1)?;
+        let texture = page.write(x, y, size, rgba, w, h);
+        PAGES.push(page);
+        Some(texture)
+    }
+}
+
+/// Перезаписывает место упакованной текстуры, если новая картинка в него помещается.
+pub(crate) fn repack(texture: &Texture, rgba: &[u8], w: u32, h: u32) -> Option<Texture> {
+    let (page, x, y) = slot(texture)?;
+    let slot = *page.slots.get(&(x, y))?;
+
+    if w == 0 || h == 0 || w + PADDING * 2 > slot.0 || h + PADDING * 2 > slot.1 {
+        return None;
+    }
+
+    Some(page.write(x, y, slot, rgba, w, h))
+}
+
+/// Освобождает место упакованной текстуры для следующих.
+pub(crate) fn free(texture: &Texture) {
+    if let Some((page, x, y)) = slot(texture) {
+        if let Some(size) = page.slots.remove(&(x, y)) {
+            page.free.push(((x, y), size));
+        }
+    }
+}
+
+/// Страница и левый верхний угол места текстуры вместе с отступом.
+fn slot(texture: &Texture) -> Option<(&'static mut Page, u32, u32)> {
+    let page = unsafe { PAGES.iter_mut().find(|p| p.id == texture.id)? };
+
+    let x = ((texture.uv[0] * PAGE as f32).round() as u32).checked_sub(PADDING)?;
+    let y = ((texture.uv[1] * PAGE as f32).round() as u32).checked_sub(PADDING)?;
+    Some((page, x, y))
+}
+
+/// Копия пикселей упакованной текстуры (или её части), `None` если она не в атласе.
+/// Страница читается с GPU, поэтому вызов дорогой.
+pub(crate) fn unpack(texture: &Texture) -> Option<(Vec<u8>, u32, u32)> {
+    if !is_page(texture.id) {
+        return None;
+    }
+
+    let x0 = (texture.uv[0] * PAGE as f32).round() as u32;
+    let y0 = (texture.uv[1] * PAGE as f32).round() as u32;
+    let x1 = (texture.uv[2] * PAGE as f32).round() as u32;
+    let y1 = (texture.uv[3] * PAGE as f32).round() as u32;
+    let (w, h) = (x1.saturating_sub(x0).max(1), y1.saturating_sub(y0).max(1));
+
+    let mut rgba = vec![0; (w * h * 4) as usize];
+
+    // Копирует пересечение прямоугольника страницы (x, y, ширина, высота) с областью
+    let mut copy = |x: u32, y: u32, width: u32, height: u32, data: &[u8]| {
+        let (from, to) = (x.max(x0), (x + width).min(x0 + w));
+        if from >= to {
+            return;
+        }
+
+        let len = ((to - from) * 4) as usize;
+        for row in y.max(y0)..(y + height).min(y0 + h) {
+            let src = (((row - y) * width + from - x) * 4) as usize;
+            let dst = (((row - y0) * w + from - x0) * 4) as usize;
+            rgba[dst..dst + len].copy_from_slice(&data[src..src + len]);
+        }
+    };
+
+    match read_texture(texture.id) {
+        Some((data, width, height)) => copy(0, 0, width, height, &data),
+        // Окна ещё нет: всё содержимое страницы пока в очереди загрузки
+        None => unsafe {
+            for (id, x, y, width, height, block) in TEXUTRES_PART.iter() {
+                if *id == texture.id {
+                    copy(*x, *y, *width, *height, block);
+                }
+            }
+        },
+    }
+
+    Some((rgba, w, h))
+}
+
+/// Id отдельной копии области, одна копия на область.
+pub(crate) fn unpacked(texture: &Texture) -> Option<usize> {
+    unsafe {
+        if let Some((_, _, id)) = UNPACKED.iter().find(|(page, uv, _)| *page == texture.id && *uv == texture.uv) {
+            return Some(*id);
+        }
+
+        let (rgba, w, h) = unpack(texture)?;
+        let (id, _, _) = add_texture_buffer(rgba, w, h);
+
+        UNPACKED.push((texture.id, texture.uv, id));
+        Some(id)
+    }
+}
+
+#[inline(always)]
+pub(crate) fn is_page(id: usize) -> bool {
+    unsafe { PAGES.iter().any(|p| p.id == id) }
+}
+
+/// Заранее собирает текстуры в атлас, даже если авто-упаковка выключена.
+/// Картинки сортируются по высоте, чтобы полки заполнялись плотнее.
+pub fn atlas(paths: &[&str]) -> Vec<Texture> {
+    let mut images: Vec<_> = paths
+        .iter()
+        .enumerate()
+        .map(|(i, path)| (i, load_image(path)))
+        .collect();
+
+    images.sort_by_key(|(_, (_, _, h))| Reverse(*h));
+
+    let mut textures: Vec<_> = images
+        .into_iter()
+        .map(|(i, (rgba, w, h))| {
+            (
+                i,
+                pack(&rgba, w, h, true).unwrap_or_else(|| super::new_texture(rgba, w, h)),
+            )
+        })
+        .collect();
+
+    textures.sort_by_key(|t| t.0);
+    textures.into_iter().map(|t| t.1).collect()
+}
@@ -1,10 +1,74 @@
-use super::{Vertex, View, WINDOW};
-use crate::object::d2::{Obj2d, DrawUpdate};
+use super::{add_texture_buffer, light::{self, LIGHTING}, material::Material, shape::{self, Gradient, Line, Stroke}, Blend, Rgba, Texture, Vertex, View, FULL_UV, WINDOW};
+use crate::object::{d2::{Obj2d, DrawUpdate}, particles::ParticleSpace, sprite::Sprite};
+
+use glam::{vec2, Affine2, Mat4, Vec2, Vec3};
+use std::{collections::HashMap, f32::consts::TAU};
+
+pub(crate) static mut RENDERS: Vec<Option<Render>> = Vec::new();
+
+/// Геометрия одного узла, из которой бэкенд собирает пачки.
+pub(crate) struct Render {
+    pub(crate) vertices: Vec<Vertex>,
+    pub(crate) indices: Vec<u16>,
+    /// Начала кусков после первого: (вершина, индекс).
+    pub(crate) parts: Vec<(usize, usize)>,
+    pub(crate) texture: Option<usize>,
+    /// Цель рендера, `None` - экран.
+    pub(crate) target: Option<usize>,
+    pub(crate) material: Option<Material>,
+    pub(crate) blend: Blend,
+    pub(crate) update: DrawUpdate,
+}
+
+impl Render {
+    pub(crate) fn new() -> Self {
+        Self {
+            vertices: vec![],
+            indices: vec![],
+            parts: vec![],
+            texture: None,
+            target: None,
+            material: None,
+            blend: Blend::Alpha,
+            update: DrawUpdate::Create,
+        }
+    }
 
-use glam::{vec2, vec3, Mat4, Vec2, Vec3};
-use std::f32::consts::TAU;
+    /// Куски геометрии, индексы каждого считаются от его первой вершины.
+    pub(crate) fn parts(&self) -> impl Iterator<Item = (&[Vertex], &[u16])> {
+        let starts = [(0, 0)].into_iter().chain(self.parts.iter().copied());
+        let ends = self.parts.iter().copied().chain([(self.vertices.len(), self.indices.len())]);
+
+        starts
+            .zip(ends)
+            .map(|((v0, i0), (v1, i1))| (&self.vertices[v0..v1], &self.indices[i0..i1]))
+    }
+}
 
-pub(crate) static mut RENDERS: Vec<Option<(Vec<Vertex>, Vec<u16>, Option<usize>, DrawUpdate)>> = Vec::new();
+/// Вершин в куске, на которые хватает индексов u16.
+pub(crate) const PART: usize = u16::MAX as usize + 1;
+
+/// Геометрия узла, которая делится на куски, когда не влезает в индексы u16.
+#[derive(Default)]
+pub(crate) struct Geometry {
+    pub(crate) vertices: Vec<Vertex>,
+    pub(crate) indices: Vec<u16>,
+    parts: Vec<(usize, usize)>,
+}
+
+impl Geometry {
+    /// Индекс первой из `n` новых вершин в текущем куске.
+    /// Если они в него не влезают, начинается новый.
+    pub(crate) fn base(&mut self, n: usize) -> u16 {
+        let len = self.vertices.len() - self.parts.last().map_or(0, |p| p.0);
+
+        if len > 0 && len + n > PART {
+            self.parts.push((self.vertices.len(), self.indices.len()));
+            return 0;
+        }
+        len as u16
+    }
+}
 
 pub(super) static mut PROJ: Mat4 = Mat4::IDENTITY;
 pub(crate) static mut MOUSE_PROJ: Vec2 = Vec2::ZERO;
@@ -59,94 +123,254 @@ impl Camera2d {
     }
 }
 
+/// Когда перерисовывать цель рендера.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Redraw {
+    Always,
+    Once,
+    /// Только после `RenderTarget::redraw`.
+    OnDemand,
+}
+
+pub(crate) struct Target {
+    pub(crate) texture: usize,
+    pub(crate) size: Vec2,
+    pub(crate) camera: Vec2,
+    pub(crate) zoom: f32,
+    pub(crate) clear: Rgba,
+    pub(crate) redraw: Redraw,
+    pub(crate) dirty: bool,
+}
+
+impl Target {
+    /// Видимая область цели в её координатах.
+    pub(crate) fn rect(&self) -> (Vec2, Vec2) {
+        let half = self.size / 2. * self.zoom;
+        (self.camera - half, self.camera + half)
+    }
+
+    /// Проекция с отражённой осью y: строки текстуры идут снизу вверх.
+    pub(crate) fn proj(&self) -> Mat4 {
+        let (min, max) = self.rect();
+        Mat4::orthographic_rh_gl(min.x, max.x, min.y, max.y, -1.0, 1.0)
+    }
+}
+
+pub(crate) static mut TARGETS: Vec<Target> = Vec::new();
+
+/// Текстура, в которую рисуется поддерево узла `viewport`,
+/// со своим размером и камерой.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RenderTarget {
+    pub(crate) id: usize,
+}
+impl RenderTarget {
+    pub fn new(width: f32, height: f32) -> Self {
+        let (w, h) = (width.max(1.) as u32, height.max(1.) as u32);
+        let (texture, _, _) = add_texture_buffer(vec![0; (w * h * 4) as usize], w, h);
+
+        unsafe {
+            TARGETS.push(Target {
+                texture,
+                size: vec2(w as f32, h as f32),
+                camera: Vec2::ZERO,
+                zoom: 1.,
+                clear: Rgba::new(0., 0., 0., 0.),
+                redraw: Redraw::Always,
+                dirty: true,
+            });
+
+            Self {
+                id: TARGETS.len() - 1,
+            }
+        }
+    }
+
+    /// Текстура цели, её можно показать любым узлом.
+    pub fn texture(&self) -> Texture {
+        let target = self.target();
+        Texture::new(target.texture, target.size.x, target.size.y, FULL_UV)
+    }
+
+    pub fn set_camera(self, pos: Vec2) -> Self {
+        self.target().camera = pos;
+        self.redraw()
+    }
+
+    pub fn get_camera(&self) -> Vec2 {
+        self.target().camera
+    }
+
+    pub fn set_zoom(self, n: f32) -> Self {
+        self.target().zoom = n;
+        self.redraw()
+    }
+
+    pub fn get_zoom(&self) -> f32 {
+        self.target().zoom
+    }
+
+    pub fn clear(self, color: Rgba) -> Self {
+        self.target().clear = color;
+        self.redraw()
+    }
+
+    pub fn update(self, redraw: Redraw) -> Self {
+        self.target().redraw = redraw;
+        self
+    }
+
+    /// Перерисовать цель в следующем кадре.
+    pub fn redraw(self) -> Self {
+        self.target().dirty = true;
+        self
+    }
+
+    /// Камера и зум, при которых цель накрывает область `min`..`max`.
+    /// Возвращает, поменялось ли что-то.
+    pub(crate) fn cover(&self, min: Vec2, max: Vec2) -> bool {
+        let target = self.target();
+        let camera = (min + max) / 2.;
+        let zoom = ((max - min) / target.size).max_element();
+
+        if target.camera == camera && target.zoom == zoom {
+            return false;
+        }
+
+        target.camera = camera;
+        target.zoom = zoom;
+        target.dirty = true;
+        true
+    }
+
+    fn target(&self) -> &'static mut Target {
+        unsafe { &mut TARGETS[self.id] }
+    }
+}
+
+/// Экранные координаты окна в координаты мира (камера, зум и `View`).
+pub fn screen_to_world(pos: Vec2) -> Vec2 {
+    unsafe {
+        if CANVAS_UPDATE {
+            upd_proj();
+        }
+
+        (pos - WINDOW / 2.) * MOUSE_PROJ + CAMERA2D
+    }
+}
+
+/// Координаты мира в экранные координаты окна.
+pub fn world_to_screen(pos: Vec2) -> Vec2 {
+    unsafe {
+        if CANVAS_UPDATE {
+            upd_proj();
+        }
+
+        (pos - CAMERA2D) / MOUSE_PROJ + WINDOW / 2.
+    }
+}
+
+/// Цвет узла и градиент заливки, который на него умножается.
+pub(crate) struct Paint<'a> {
+    pub(crate) color: [f32; 4],
+    pub(crate) gradient: Option<&'a Gradient>,
+}
+
+impl Paint<'_> {
+    #[inline(always)]
+    fn at(&self, p: Vec2) -> [f32; 4] {
+        match self.gradient {
+            Some(g) => {
+                let c = g.at(p);
+                [c[0] * self.color[0], c[1] * self.color[1], c[2] * self.color[2], c[3] * self.color[3]]
+            }
+            None => self.color,
+        }
+    }
+
+    /// Колец внутри фигуры: цвет между вершинами меняется линейно,
+    /// и без внутренних вершин градиент с несколькими точками ломается.
+    #[inline(always)]
+    fn rings(&self) -> usize {
+        if self.gradient.is_some() { 8 } else { 1 }
+    }
+}
+
 #[inline(always)]
 pub(crate) fn draw(
     id: usize,
-    pos: Vec2,
+    transform: &Affine2,
     obj: &Obj2d,
-    scale: Vec2,
-    rotation: f32,
     offset: Vec2,
-    color: [f32; 4],
+    paint: &Paint,
+    stroke: Option<Stroke>,
+    segments: usize,
 ) {
-    match obj {
-        Obj2d::None => {}
-        Obj2d::Circle(r) => {
-            let mut vertices: Vec<Vertex> = Vec::new();
-            let mut indices: Vec<u16> = Vec::new();
+    // Геометрия строится в локальных координатах узла и переводится в мир
+    let point = |p: Vec2| transform.transform_point2(p + offset).extend(0.);
 
-            let segments = 40;
+    let mut geometry = Geometry::default();
 
-            vertices.push(Vertex {
-                pos: vec3(pos.x, pos.y, 0.),
-                color,
-                uv: Vec2::new(0., 0.),
-            });
+    // Замкнутый контур фигуры для обводки
+    let mut outline: Vec<Vec2> = Vec::new();
 
-            let offset = if offset == vec2(0., 0.) {
-                offset
-            } else {
-                offset * r * scale
-            };
+    match obj {
+        Obj2d::None => {}
+        Obj2d::Circle(r) => {
+            outline = shape::ellipse(vec2(*r, *r), segments);
+            fill_fan(&mut geometry, &outline, true, paint, &point);
+        }
+        Obj2d::Ellipse(rx, ry) => {
+            outline = shape::ellipse(vec2(*rx, *ry), segments);
+            fill_fan(&mut geometry, &outline, true, paint, &point);
+        }
+        Obj2d::Pie(r, start, end) => {
+            let arc = shape::arc(*r, *start, *end, segments);
+            fill_fan(&mut geometry, &arc, false, paint, &point);
 
-            for i in 0..segments {
-                let theta = i as f32 / segments as f32 * TAU;
-                let x = r * theta.cos() * scale.x + offset.x;
-                let y = r * theta.sin() * scale.y + offset.y;
-                let p = rotate(vec2(x, y), pos, rotation);
-                vertices.push(Vertex {
-                    pos: p,
-                    color: color,
+            outline.push(Vec2::ZERO);
+            outline.extend(arc);
+        }
+        Obj2d::Ring(r, inner) => {
+            let rings = paint.rings();
+            let circle = shape::ellipse(Vec2::ONE, segments);
+
+            let base = geometry.base((rings + 1) * segments);
+            for j in 0..=rings {
+                let radius = inner + (r - inner) * j as f32 / rings as f32;
+                geometry.vertices.extend(circle.iter().map(|p| Vertex {
+                    pos: point(*p * radius),
+                    color: paint.at(*p * radius),
                     uv: Vec2::new(0., 0.),
-                });
+                }));
             }
 
-            for i in 1..segments {
-                indices.extend([0, i as u16, (i + 1) as u16]);
+            let n = segments as u16;
+            for j in 0..rings as u16 {
+                let (a, b) = (base + j * n, base + (j + 1) * n);
+                for i in 0..n {
+                    let k = (i + 1) % n;
+                    geometry.indices.extend([a + i, a + k, b + i, a + k, b + k, b + i]);
+                }
             }
-            indices.extend([0, segments, 1]);
 
-            render(id, vertices, indices);
+            if let Some(s) = stroke {
+                let hole: Vec<Vec2> = circle.iter().map(|p| *p * *inner).collect();
+                outline_stroke(&mut geometry, &hole, &s, &point);
+                outline = circle.iter().map(|p| *p * *r).collect();
+            }
+        }
+        Obj2d::Arc(r, start, end, line) => {
+            polyline(&mut geometry, &shape::arc(*r, *start, *end, segments), line, paint, stroke, &point)
         }
         Obj2d::Rect(w, h, r) => {
-            let w = (w * scale.x) / 2.;
-            let h = (h * scale.y) / 2.;
-
-            let mut vertices: Vec<Vertex> = Vec::new();
-            let mut indices: Vec<u16> = Vec::new();
-
-            let offset = if offset == vec2(0., 0.) {
-                offset
-            } else {
-                offset * vec2(w, h) * scale
-            };
+            let w = w / 2.;
+            let h = h / 2.;
 
             if *r <= 1. {
-                vertices.extend([
-                    Vertex {
-                        pos: rotate(vec2(-w + offset.x, -h + offset.y), pos, rotation),
-                        color: color,
-                        uv: Vec2::new(0., 0.),
-                    },
-                    Vertex {
-                        pos: rotate(vec2(w + offset.x, -h + offset.y), pos, rotation),
-                        color: color,
-                        uv: Vec2::new(0., 0.),
-                    },
-                    Vertex {
-                        pos: rotate(vec2(w + offset.x, h + offset.y), pos, rotation),
-                        color: color,
-                        uv: Vec2::new(0., 0.),
-                    },
-                    Vertex {
-                        pos: rotate(vec2(-w + offset.x, h + offset.y), pos, rotation),
-                        color: color,
-                        uv: Vec2::new(0., 0.),
-                    },
-                ]);
-                indices.extend(vec![0, 1, 3, 1, 2, 3]);
+                outline = vec![vec2(-w, -h), vec2(w, -h), vec2(w, h), vec2(-w, h)];
             } else {
-                let segments = 40;
+                let segments = (segments / 4).max(1) * 4;
 
                 let half_segments = segments / 4;
 
@@ -157,91 +381,334 @@ pub(crate) fn draw(
                     vec2(w - r, -h + r),  // top-right
                 ];
 
-                vertices.push(Vertex {
-                    pos: vec3(pos.x, pos.y, 0.),
-                    color,
-                    uv: Vec2::new(0., 0.),
-                });
-
                 for (corner_index, &center) in corner_centers.iter().enumerate() {
                     for i in 0..half_segments {
                         let theta =
                             (corner_index * half_segments + i) as f32 / segments as f32 * TAU;
-                        let x = center.x + r * theta.cos() * scale.x + offset.x;
-                        let y = center.y + r * theta.sin() * scale.y + offset.y;
-                        let p = rotate(vec2(x, y), pos, rotation);
-                        vertices.push(Vertex {
-                            pos: p,
-                            color: color,
-                            uv: Vec2::new(0., 0.),
-                        });
+                        outline.push(center + vec2(r * theta.cos(), r * theta.sin()));
                     }
                 }
+            }
+
+            if *r <= 1. && paint.gradient.is_none() {
+                geometry.vertices.extend(outline.iter().map(|p| Vertex {
+                    pos: point(*p),
+                    color: paint.color,
+                    uv: Vec2::new(0., 0.),
+                }));
+                geometry.indices.extend([0, 1, 3, 1, 2, 3]);
+            } else {
+                fill_fan(&mut geometry, &outline, true, paint, &point);
+            }
+        }
+        Obj2d::Texture(t)
+        | Obj2d::Text(_, _, _, t)
+        | Obj2d::Sprite(Sprite { texture: t, .. }) => {
+            let w = t.width / 2.;
+            let h = t.height / 2.;
+
+            for (x, y, u, v) in [(-w, -h, 0., 0.), (w, -h, 1., 0.), (w, h, 1., 1.), (-w, h, 0., 1.)] {
+                geometry.vertices.push(Vertex {
+                    pos: point(vec2(x, y)),
+                    color: paint.at(vec2(x, y)),
+                    uv: t.uv(vec2(u, v)),
+                });
+            }
+            geometry.indices.extend([0, 1, 3, 1, 2, 3]);
+        }
+        Obj2d::NineSlice(n) => {
+            let t = &n.texture;
+            let [left, up, right, down] = n.border;
+
+            let columns = slices(n.width, t.width, left, right, n.tile);
+            let rows = slices(n.height, t.height, up, down, n.tile);
+
+            for &(y0, y1, v0, v1) in &rows {
+                for &(x0, x1, u0, u1) in &columns {
+                    let i = geometry.base(4);
+
+                    for (x, y, u, v) in [(x0, y0, u0, v0), (x1, y0, u1, v0), (x1, y1, u1, v1), (x0, y1, u0, v1)] {
+                        geometry.vertices.push(Vertex {
+                            pos: point(vec2(x, y)),
+                            color: paint.at(vec2(x, y)),
+                            uv: t.uv(vec2(u, v)),
+                        });
+                    }
 
-                for i in 1..segments {
-                    indices.extend([0, i as u16, (i + 1) as u16]);
+                    geometry.indices.extend([i, i + 1, i + 3, i + 1, i + 2, i + 3]);
                 }
-                indices.extend([0, segments as u16, 1]);
+            }
+        }
+        Obj2d::Polygon(points) => {
+            let mut fill = (points.clone(), shape::triangulate(points));
+            if paint.gradient.is_some() {
+                fill = shape::subdivide(fill);
             }
 
-            render(id, vertices, indices);
+            add_shape(&mut geometry, fill, paint, &point);
+            outline = points.clone();
+        }
+        Obj2d::Polyline(points, line) => {
+            polyline(&mut geometry, points, line, paint, stroke, &point)
+        }
+        Obj2d::Mesh { vertices: mesh, indices: mesh_indices, texture } => {
+            geometry.vertices.extend(mesh.iter().map(|(p, c, uv)| {
+                let tint = paint.at(*p);
+                let c = c.get();
+
+                Vertex {
+                    pos: point(*p),
+                    color: [0, 1, 2, 3].map(|j| c[j] * tint[j]),
+                    uv: texture.map_or(*uv, |t| t.uv(*uv)),
+                }
+            }));
+            geometry.indices.extend(mesh_indices);
         }
-        Obj2d::Texture(t) | Obj2d::Text(_, _, _, t) => {
-            let w = (t.width as f32 * scale.x) / 2.;
-            let h = (t.height as f32 * scale.y) / 2.;
+        Obj2d::TileMap(map) => {
+            // При сильном отдалении чанки не влезают в один кусок и делятся на несколько
+            for &key in &map.visible {
+                for quad in map.mesh(key).chunks(4) {
+                    let base = geometry.base(4);
+
+                    geometry.vertices.extend(quad.iter().map(|(p, uv)| Vertex {
+                        pos: point(*p),
+                        color: paint.at(*p),
+                        uv: map.texture.uv(*uv),
+                    }));
+                    geometry.indices.extend([base, base + 1, base + 3, base + 1, base + 2, base + 3]);
+                }
+            }
+        }
+        Obj2d::Particles(p) => {
+            // Частицы мира уже в его координатах, мимо трансформа узла
+            let world = |q: Vec2| q.extend(0.);
+            let place: &dyn Fn(Vec2) -> Vec3 = match p.space {
+                ParticleSpace::Local => &point,
+                ParticleSpace::World => &world,
+            };
+            let round = p.vertices() == 9;
+
+            for particle in &p.particles {
+                let t = particle.time();
+                let c = particle.position;
+                let half = p.size * p.scale_at(t) / 2.;
+
+                let color = p.color_at(t);
+                let tint = paint.at(c);
+                let color = [0, 1, 2, 3].map(|j| color[j] * tint[j]);
 
-            let offset = offset * vec2(w, h) * scale;
+                let base = geometry.base(p.vertices());
 
-            render(id, 
-                vec![
-                    Vertex {
-                        pos: rotate(vec2(-w + offset.x, -h + offset.y), pos, rotation),
-                        color: color,
+                if round {
+                    geometry.vertices.push(Vertex {
+                        pos: place(c),
+                        color,
                         uv: Vec2::new(0., 0.),
-                    },
-                    Vertex {
-                        pos: rotate(vec2(w + offset.x, -h + offset.y), pos, rotation),
-                        color: color,
-                        uv: Vec2::new(1., 0.),
-                    },
-                    Vertex {
-                        pos: rotate(vec2(w + offset.x, h + offset.y), pos, rotation),
-                        color: color,
-                        uv: Vec2::new(1., 1.),
-                    },
-                    Vertex {
-                        pos: rotate(vec2(-w + offset.x, h + offset.y), pos, rotation),
-                        color: color,
-                        uv: Vec2::new(0., 1.),
-                    },
-                ],
-                vec![0, 1, 3, 1, 2, 3],
-            );
+                    });
+                    geometry.vertices.extend(shape::ellipse(Vec2::splat(half), 8).into_iter().map(|q| Vertex {
+                        pos: place(c + q),
+                        color,
+                        uv: Vec2::new(0., 0.),
+                    }));
+                    geometry.indices.extend((1..=8).flat_map(|i| [base, base + i, base + i % 8 + 1]));
+                } else {
+                    for (x, y) in [(0., 0.), (1., 0.), (1., 1.), (0., 1.)] {
+                        geometry.vertices.push(Vertex {
+                            pos: place(c + (vec2(x, y) - 0.5) * half * 2.),
+                            color,
+                            uv: p.texture.map_or(Vec2::new(0., 0.), |t| t.uv(vec2(x, y))),
+                        });
+                    }
+                    geometry.indices.extend([base, base + 1, base + 3, base + 1, base + 2, base + 3]);
+                }
+            }
+        }
+        Obj2d::Light(l) => {
+            if unsafe { LIGHTING }.is_some() {
+                light::geometry(l, transform, offset, &mut geometry);
+            }
         }
+        Obj2d::Lighting(target) => {
+            // Буфер света накрывает область своей цели, мимо трансформа узла
+            let texture = target.texture();
+            let (min, max) = unsafe { TARGETS[target.id].rect() };
+
+            for (x, y) in [(0., 0.), (1., 0.), (1., 1.), (0., 1.)] {
+                geometry.vertices.push(Vertex {
+                    pos: (min + (max - min) * vec2(x, y)).extend(0.),
+                    color: paint.color,
+                    uv: texture.uv(vec2(x, y)),
+                });
+            }
+            geometry.indices.extend([0, 1, 3, 1, 2, 3]);
+        }
+    }
+
+    if let Some(s) = stroke {
+        if !outline.is_empty() {
+            outline_stroke(&mut geometry, &outline, &s, &point);
+        }
+    }
+
+    if !matches!(obj, Obj2d::None) {
+        render(id, geometry);
     }
 }
 
-#[inline(always)]
-fn rotate(p: Vec2, center: Vec2, rotation: f32) -> Vec3 {
-    if rotation != 0. {
-        let s = rotation.sin();
-        let c = rotation.cos();
-
-        vec3(
-            (p.x * c - p.y * s) + center.x,
-            (p.x * s + p.y * c) + center.y,
-            0.,
-        )
+/// Заливка звёздного от центра контура веером, с кольцами под градиент.
+/// Незамкнутый контур (дуга сектора) замыкается через центр.
+fn fill_fan(
+    geometry: &mut Geometry,
+    outline: &[Vec2],
+    closed: bool,
+    paint: &Paint,
+    point: &impl Fn(Vec2) -> Vec3,
+) {
+    let rings = paint.rings();
+    let outline = if rings > 1 {
+        shape::densify(outline, closed)
     } else {
-        vec3(p.x + center.x, p.y + center.y, 0.)
+        outline.to_vec()
+    };
+
+    let base = geometry.base(1 + outline.len() * rings);
+    geometry.vertices.push(Vertex {
+        pos: point(Vec2::ZERO),
+        color: paint.at(Vec2::ZERO),
+        uv: Vec2::new(0., 0.),
+    });
+
+    for j in 1..=rings {
+        let k = j as f32 / rings as f32;
+        geometry.vertices.extend(outline.iter().map(|p| Vertex {
+            pos: point(*p * k),
+            color: paint.at(*p * k),
+            uv: Vec2::new(0., 0.),
+        }));
+    }
+
+    let n = outline.len() as u16;
+    let edges = if closed { n } else { n - 1 };
+    let next = |i: u16| (i + 1) % n;
+
+    for i in 0..edges {
+        geometry.indices.extend([base, base + 1 + i, base + 1 + next(i)]);
     }
+
+    for j in 1..rings as u16 {
+        let (a, b) = (base + 1 + (j - 1) * n, base + 1 + j * n);
+        for i in 0..edges {
+            geometry.indices.extend([a + i, a + next(i), b + i, a + next(i), b + next(i), b + i]);
+        }
+    }
+}
+
+fn polyline(
+    geometry: &mut Geometry,
+    points: &[Vec2],
+    line: &Line,
+    paint: &Paint,
+    stroke: Option<Stroke>,
+    point: &impl Fn(Vec2) -> Vec3,
+) {
+    // Обводка ломаной - более широкая линия под ней
+    if let Some(s) = stroke {
+        let under = Line {
+            width: line.width + s.width * 2.,
+            ..*line
+        };
+        let color = Paint { color: s.color.get(), gradient: None };
+        add_shape(geometry, shape::stroke(points, &under, false), &color, point);
+    }
+
+    add_shape(geometry, shape::stroke(points, line, false), paint, point);
+}
+
+/// Замкнутая обводка по контуру фигуры поверх заливки.
+fn outline_stroke(
+    geometry: &mut Geometry,
+    outline: &[Vec2],
+    stroke: &Stroke,
+    point: &impl Fn(Vec2) -> Vec3,
+) {
+    let line = Line::new(stroke.width).join(stroke.join);
+    let color = Paint { color: stroke.color.get(), gradient: None };
+    add_shape(geometry, shape::stroke(outline, &line, true), &color, point);
+}
+
+fn add_shape(
+    geometry: &mut Geometry,
+    (points, shape): (Vec<Vec2>, Vec<u32>),
+    paint: &Paint,
+    point: &impl Fn(Vec2) -> Vec3,
+) {
+    let vertex = |p: Vec2| Vertex {
+        pos: point(p),
+        color: paint.at(p),
+        uv: Vec2::new(0., 0.),
+    };
+
+    if points.len() <= PART {
+        let base = geometry.base(points.len());
+        geometry.vertices.extend(points.into_iter().map(vertex));
+        geometry.indices.extend(shape.into_iter().map(|i| i as u16 + base));
+        return;
+    }
+
+    // Большая фигура делится по треугольникам, общие вершины копируются в каждый кусок
+    let mut remap: HashMap<u32, u16> = HashMap::new();
+    for triangle in shape.chunks(3) {
+        let fresh = triangle.iter().filter(|i| !remap.contains_key(i)).count();
+        if geometry.base(fresh) == 0 {
+            remap.clear();
+        }
+
+        for i in triangle {
+            let index = *remap.entry(*i).or_insert_with(|| {
+                let index = geometry.base(1);
+                geometry.vertices.push(vertex(points[*i as usize]));
+                index
+            });
+            geometry.indices.push(index);
+        }
+    }
+}
+
+/// Отрезки одной оси nine-slice: (от, до, u от, u до).
+/// Края сжимаются, если не помещаются в размер.
+fn slices(size: f32, tex: f32, a: f32, b: f32, tile: bool) -> Vec<(f32, f32, f32, f32)> {
+    let k = if a + b > size { size / (a + b) } else { 1. };
+
+    let start = -size / 2.;
+    let (m0, m1) = (start + a * k, size / 2. - b * k);
+    let (u0, u1) = (a / tex, 1. - b / tex);
+
+    let mut out = vec![(start, m0, 0., u0)];
+
+    let src = tex - a - b;
+    if tile && src > 0. {
+        let mut x = m0;
+        while x < m1 {
+            let len = (m1 - x).min(src);
+            out.push((x, x + len, u0, u0 + len / tex));
+            x += len;
+        }
+    } else {
+        out.push((m0, m1, u0, u1));
+    }
+
+    out.push((m1, size / 2., u1, 1.));
+    out.retain(|p| p.1 > p.0);
+    out
 }
 
 #[inline(always)]
-fn render(id: usize, mut vert: Vec<Vertex>, mut indi: Vec<u16>) {
+fn render(id: usize, geometry: Geometry) {
     unsafe {
-        RENDERS[id].as_mut().unwrap().0 = vert;
-        RENDERS[id].as_mut().unwrap().1 = indi;
+        let render = RENDERS[id].as_mut().unwrap();
+        render.vertices = geometry.vertices;
+        render.indices = geometry.indices;
+        render.parts = geometry.parts;
         /*let needs_new_batch = match RENDERS.last() {
             Some((_, _, last_img)) => *last_img != img,
             None => true,
@@ -267,11 +734,49 @@ fn render(id: usize, mut vert: Vec<Vertex>, mut indi: Vec<u16>) {
 #[inline(always)]
 pub(crate) fn new_render() -> usize {
     unsafe {
-        RENDERS.push(Some((vec![], vec![], None, DrawUpdate::Create)));
+        RENDERS.push(Some(Render::new()));
         RENDERS.len() - 1
     }
 }
 
+/// Убирает геометрию узла из кадра, не освобождая слот.
+#[inline(always)]
+pub(crate) fn clear_render(id: usize) {
+    unsafe {
+        if let Some(render) = RENDERS.get_mut(id).and_then(Option::as_mut) {
+            render.vertices.clear();
+            render.indices.clear();
+            render.parts.clear();
+
+            if render.update != DrawUpdate::Create {
+                render.update = DrawUpdate::Update;
+            }
+        }
+    }
+}
+
+/// Пикселей цели (или экрана) на единицу мира.
+#[inline(always)]
+pub(crate) fn pixel_scale(target: Option<usize>) -> f32 {
+    unsafe {
+        match target {
+            Some(id) => 1. / TARGETS[id].zoom,
+            None => WINDOW.x / (CANVAS_PROJ.x * 2.),
+        }
+    }
+}
+
+/// Видимая область камеры в координатах мира, или области цели рендера.
+#[inline(always)]
+pub(crate) fn view_rect(target: Option<usize>) -> (Vec2, Vec2) {
+    unsafe {
+        match target {
+            Some(id) => TARGETS[id].rect(),
+            None => (CAMERA2D - CANVAS_PROJ, CAMERA2D + CANVAS_PROJ),
+        }
+    }
+}
+
 #[inline(always)]
 pub(crate) fn del_render(id: usize) {
     unsafe {
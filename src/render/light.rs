@@ -0,0 +1,227 @@
+use super::{d2::Geometry, shape, Rgba, Vertex};
+use crate::object::d2::inside;
+
+use glam::{vec2, Affine2, Vec2};
+use std::f32::consts::{PI, TAU};
+
+/// Цель рендера буфера света, в неё рисуются все источники.
+pub(crate) static mut LIGHTING: Option<usize> = None;
+
+/// Формы теней этого кадра в координатах мира.
+pub(crate) static mut OCCLUDERS: Vec<Vec<Vec2>> = Vec::new();
+/// Тени изменились с прошлого кадра, источники с тенями перестраиваются.
+pub(crate) static mut SHADOWS_UPDATE: bool = false;
+
+/// Точек света на краю мягкой тени.
+const SOFT: usize = 8;
+/// Наименьшее затухание: у края света `pow(0, 0)` не определена.
+const MIN_FALLOFF: f32 = 0.01;
+/// Точек в одном куске веера: вместе с центром ровно кусок геометрии.
+const FAN: usize = u16::MAX as usize;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Shadow {
+    None,
+    Hard,
+    /// Мягкая тень от источника радиуса `size`: полутень растёт с ним.
+    Soft(f32),
+}
+
+/// Источник света. Конус прожектора направлен по оси x узла.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Light {
+    pub color: Rgba,
+    pub radius: f32,
+    pub energy: f32,
+    /// Степень затухания к краю: 1 - линейно, больше - быстрее.
+    /// Меньше 0.01 считается как 0.01.
+    pub falloff: f32,
+    /// Полный угол конуса в радианах, `None` - во все стороны.
+    pub cone: Option<f32>,
+    pub shadow: Shadow,
+}
+
+impl Light {
+    pub fn point(radius: f32) -> Self {
+        Self {
+            color: Rgba::new(1., 1., 1., 1.),
+            radius,
+            energy: 1.,
+            falloff: 2.,
+            cone: None,
+            shadow: Shadow::None,
+        }
+    }
+
+    pub fn spot(radius: f32, angle: f32) -> Self {
+        Self {
+            cone: Some(angle.clamp(0.001, TAU)),
+            ..Self::point(radius)
+        }
+    }
+
+    pub fn color(mut self, color: Rgba) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn energy(mut self, energy: f32) -> Self {
+        self.energy = energy;
+        self
+    }
+
+    pub fn falloff(mut self, falloff: f32) -> Self {
+        self.falloff = falloff.max(MIN_FALLOFF);
+        self
+    }
+
+    pub fn shadow(mut self, shadow: Shadow) -> Self {
+        self.shadow = shadow;
+        self
+    }
+}
+
+/// Освещённая область источника: веер лучей до ближайшей тени.
+/// uv вершин - смещение от центра в долях радиуса, затухание считает шейдер
+/// света, степень затухания передаётся в альфе цвета.
+pub(crate) fn geometry(light: &Light, transform: &Affine2, offset: Vec2, geometry: &mut Geometry) {
+    let center = transform.transform_point2(offset);
+    let radius = light.radius.max(f32::EPSILON);
+
+    let dir = transform.matrix2 * Vec2::X;
+    let dir = dir.y.atan2(dir.x);
+
+    // Отрезки теней в пределах света, кроме форм, внутри которых сам свет
+    let mut edges: Vec<(Vec2, Vec2)> = Vec::new();
+
+    if light.shadow != Shadow::None {
+        for points in unsafe { OCCLUDERS.iter() } {
+            let near = points.iter().any(|p| p.distance(center) < radius * 2.)
+                || points.windows(2).any(|w| segment_distance(center, w[0], w[1]) < radius * 2.);
+
+            if points.len() < 2 || !near || inside(center, points) {
+                continue;
+            }
+
+            for (i, &a) in points.iter().enumerate() {
+                edges.push((a, points[(i + 1) % points.len()]));
+            }
+        }
+    }
+
+    let samples: Vec<Vec2> = match light.shadow {
+        Shadow::Soft(size) if size > 0. && !edges.is_empty() => (0..SOFT)
+            .map(|i| {
+                let a = i as f32 / SOFT as f32 * TAU;
+                center + vec2(a.cos(), a.sin()) * size
+            })
+            .collect(),
+        _ => vec![center],
+    };
+
+    let weight = light.energy / samples.len() as f32;
+    let color = [
+        light.color.r * light.color.a * weight,
+        light.color.g * light.color.a * weight,
+        light.color.b * light.color.a * weight,
+        light.falloff.max(MIN_FALLOFF),
+    ];
+
+    let uv = |p: Vec2| (p - center) / radius;
+    let sweep = light.cone.unwrap_or(TAU);
+    let rays = (shape::segments(radius) as f32 * sweep / TAU).ceil().clamp(4., 96.) as usize;
+
+    for s in samples {
+        let reach = radius + s.distance(center);
+
+        let mut angles: Vec<f32> = (0..=rays).map(|i| i as f32 / rays as f32 * sweep - sweep / 2.).collect();
+
+        // Лучи чуть мимо углов теней, чтобы тень начиналась точно с угла
+        for &(a, _) in &edges {
+            if a.distance(s) < reach {
+                let d = a - s;
+                let angle = wrap(d.y.atan2(d.x) - dir);
+                angles.extend([angle - 1e-4, angle, angle + 1e-4]);
+            }
+        }
+
+        if light.cone.is_some() {
+            angles.retain(|a| a.abs() <= sweep / 2.);
+        }
+        angles.sort_by(f32::total_cmp);
+        angles.dedup();
+
+        let mut points: Vec<Vec2> = angles
+            .iter()
+            .map(|angle| {
+                let angle = angle + dir;
+                let d = vec2(angle.cos(), angle.sin());
+                s + d * cast(s, d, reach, &edges)
+            })
+            .collect();
+
+        if light.cone.is_none() && !points.is_empty() {
+            points.push(points[0]);
+        }
+
+        // Веер режется на куски по u16 индексов, соседние делят крайнюю точку
+        let mut start = 0;
+        while start + 1 < points.len() {
+            let end = (start + FAN).min(points.len());
+            let fan = &points[start..end];
+            let base = geometry.base(fan.len() + 1);
+
+            geometry.vertices.push(Vertex {
+                pos: s.extend(0.),
+                color,
+                uv: uv(s),
+            });
+            geometry.vertices.extend(fan.iter().map(|&p| Vertex {
+                pos: p.extend(0.),
+                color,
+                uv: uv(p),
+            }));
+
+            for i in 0..fan.len() as u16 - 1 {
+                geometry.indices.extend([base, base + 1 + i, base + 2 + i]);
+            }
+
+            start = end - 1;
+        }
+    }
+}
+
+/// Расстояние по лучу до ближайшего отрезка, не дальше `reach`.
+fn cast(origin: Vec2, dir: Vec2, reach: f32, edges: &[(Vec2, Vec2)]) -> f32 {
+    let mut t = reach;
+
+    for &(a, b) in edges {
+        let e = b - a;
+        let denom = dir.perp_dot(e);
+        if denom.abs() < 1e-9 {
+            continue;
+        }
+
+        let w = a - origin;
+        let along = w.perp_dot(e) / denom;
+        let u = w.perp_dot(dir) / denom;
+
+        if along > 0. && (0. ..=1.).contains(&u) && along < t {
+            t = along;
+        }
+    }
+
+    t
+}
+
+fn segment_distance(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let e = b - a;
+    let t = ((p - a).dot(e) / e.length_squared().max(f32::EPSILON)).clamp(0., 1.);
+    p.distance(a + e * t)
+}
+
+/// Угол в -PI..PI.
+#[inline(always)]
+fn wrap(a: f32) -> f32 {
+    (a + PI).rem_euclid(TAU) - PI
+}
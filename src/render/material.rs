@@ -0,0 +1,341 @@
+use super::{Rgba, Texture};
+
+use glam::{Vec2, Vec3};
+
+/// Значение uniform-переменной материала.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Uniform {
+    Float(f32),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Vec4([f32; 4]),
+}
+
+impl Uniform {
+    pub(crate) fn floats(&self) -> Vec<f32> {
+        match self {
+            Uniform::Float(x) => vec![*x],
+            Uniform::Vec2(v) => v.to_array().to_vec(),
+            Uniform::Vec3(v) => v.to_array().to_vec(),
+            Uniform::Vec4(v) => v.to_vec(),
+        }
+    }
+}
+
+impl From<f32> for Uniform {
+    fn from(x: f32) -> Self {
+        Uniform::Float(x)
+    }
+}
+impl From<Vec2> for Uniform {
+    fn from(v: Vec2) -> Self {
+        Uniform::Vec2(v)
+    }
+}
+impl From<Vec3> for Uniform {
+    fn from(v: Vec3) -> Self {
+        Uniform::Vec3(v)
+    }
+}
+impl From<Rgba> for Uniform {
+    fn from(c: Rgba) -> Self {
+        Uniform::Vec4(c.get())
+    }
+}
+
+/// Исходники шейдера и объявленные в нём переменные.
+#[derive(Clone)]
+pub(crate) struct Shader {
+    pub(crate) vertex: Option<String>,
+    pub(crate) fragment: String,
+    pub(crate) metal: Option<String>,
+    /// Имена переменных и значения по умолчанию (задают тип).
+    pub(crate) uniforms: Vec<(String, Uniform)>,
+    pub(crate) images: Vec<String>,
+    /// У шейдера есть копии материала: новые объявления уходят в его копию.
+    shared: bool,
+    /// Ошибка сборки шейдера бэкендом.
+    pub(crate) error: Option<String>,
+}
+
+pub(crate) static mut SHADERS: Vec<Shader> = Vec::new();
+
+static mut BUILTINS: [Option<Material>; 4] = [None, None, None, None];
+
+/// Шейдер узла и его значения. Копии делят шейдер, но не значения.
+/// Объявление переменной или текстуры у материала с копиями
+/// (встроенного или уже отданного узлу) переносит его на свой шейдер.
+///
+/// Во фрагментном шейдере (GLSL 100) доступны `varying color`, `varying uv`,
+/// `uniform sampler2D tex`, `uniform float time` и объявленные переменные.
+/// Основная текстура узла с доп. текстурами не берётся из атласа,
+/// поэтому `uv` у них общие. Материалы рисует бэкенд miniquad.
+#[derive(PartialEq, Debug)]
+pub struct Material {
+    pub(crate) shader: usize,
+    pub(crate) uniforms: Vec<Uniform>,
+    pub(crate) images: Vec<Option<usize>>,
+}
+
+impl Material {
+    pub fn new(fragment: &str) -> Self {
+        unsafe {
+            SHADERS.push(Shader {
+                vertex: None,
+                fragment: fragment.to_string(),
+                metal: None,
+                uniforms: Vec::new(),
+                images: Vec::new(),
+                shared: false,
+                error: None,
+            });
+
+            Self {
+                shader: SHADERS.len() - 1,
+                uniforms: Vec::new(),
+                images: Vec::new(),
+            }
+        }
+    }
+
+    /// Свой вершинный шейдер (GLSL 100) вместо стандартного.
+    pub fn vertex(mut self, source: &str) -> Self {
+        self.declare().vertex = Some(source.to_string());
+        self
+    }
+
+    /// Исходник для Metal. Без него на Metal узел рисуется обычным шейдером.
+    pub fn metal(mut self, source: &str) -> Self {
+        self.declare().metal = Some(source.to_string());
+        self
+    }
+
+    /// Объявляет переменную со значением по умолчанию.
+    pub fn uniform(mut self, name: &str, value: impl Into<Uniform>) -> Self {
+        let value = value.into();
+        self.declare().uniforms.push((name.to_string(), value));
+        self.uniforms.push(value);
+        self
+    }
+
+    /// Объявляет дополнительную текстуру, читается по тем же uv.
+    pub fn image(mut self, name: &str, texture: &Texture) -> Self {
+        self.declare().images.push(name.to_string());
+        self.images.push(Some(texture.unpacked().id));
+        self
+    }
+
+    /// Ошибка сборки шейдера, если бэкенд уже пробовал его собрать.
+    pub fn error(&self) -> Option<&str> {
+        self.shader().error.as_deref()
+    }
+
+    pub fn set(&mut self, name: &str, value: impl Into<Uniform>) {
+        let id = self.find(name);
+        self.uniforms[id] = value.into();
+    }
+
+    pub fn get(&self, name: &str) -> Uniform {
+        self.uniforms[self.find(name)]
+    }
+
+    pub fn set_image(&mut self, name: &str, texture: &Texture) {
+        let id = self
+            .shader()
+            .images
+            .iter()
+            .position(|n| n == name)
+            .unwrap_or_else(|| panic!("Not found image ({})", name));
+        self.images[id] = Some(texture.unpacked().id);
+    }
+
+    /// Оттенки серого, `amount` от 0 до 1.
+    pub fn grayscale() -> Self {
+        builtin(0, || Material::new(GRAYSCALE).uniform("amount", 1.))
+    }
+
+    /// Заливка цветом `flash` (по умолчанию белый), сила в его альфе.
+    pub fn flash() -> Self {
+        builtin(1, || Material::new(FLASH).uniform("flash", Rgba::new(1., 1., 1., 0.)))
+    }
+
+    /// Растворение по шуму, `amount` от 0 до 1, кромка цвета `edge`.
+    pub fn dissolve() -> Self {
+        builtin(2, || {
+            Material::new(DISSOLVE)
+                .uniform("amount", 0.)
+                .uniform("edge", Rgba::new(1., 0.6, 0.2, 1.))
+        })
+    }
+
+    /// Шейдер источников света: затухание от центра по uv.
+    pub(crate) fn light() -> Self {
+        builtin(3, || Material::new(LIGHT).vertex(LIGHT_VERTEX).metal(LIGHT_METAL))
+    }
+
+    fn shader(&self) -> &'static mut Shader {
+        unsafe { &mut SHADERS[self.shader] }
+    }
+
+    /// Шейдер для новых объявлений: общий шейдер сначала копируется,
+    /// чтобы раскладка переменных у других копий не менялась.
+    fn declare(&mut self) -> &'static mut Shader {
+        let shader = self.shader();
+
+        if shader.shared {
+            unsafe {
+                SHADERS.push(Shader {
+                    shared: false,
+                    error: None,
+                    ..shader.clone()
+                });
+                self.shader = SHADERS.len() - 1;
+            }
+        }
+
+        self.shader()
+    }
+
+    fn find(&self, name: &str) -> usize {
+        self.shader()
+            .uniforms
+            .iter()
+            .position(|(n, _)| n == name)
+            .unwrap_or_else(|| panic!("Not found uniform ({})", name))
+    }
+}
+
+impl Clone for Material {
+    fn clone(&self) -> Self {
+        self.shader().shared = true;
+
+        Self {
+            shader: self.shader,
+            uniforms: self.uniforms.clone(),
+            images: self.images.clone(),
+        }
+    }
+}
+
+/// Встроенные материалы делят один шейдер на всех.
+fn builtin(slot: usize, make: fn() -> Material) -> Material {
+    unsafe { BUILTINS[slot].get_or_insert_with(make).clone() }
+}
+
+const GRAYSCALE: &str = r#"#version 100
+precision mediump float;
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D tex;
+uniform float amount;
+
+void main() {
+    vec4 c = texture2D(tex, uv) * color;
+    float g = dot(c.rgb, vec3(0.299, 0.587, 0.114));
+    gl_FragColor = vec4(mix(c.rgb, vec3(g), amount), c.a);
+}"#;
+
+const FLASH: &str = r#"#version 100
+precision mediump float;
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D tex;
+uniform vec4 flash;
+
+void main() {
+    vec4 c = texture2D(tex, uv) * color;
+    gl_FragColor = vec4(mix(c.rgb, flash.rgb, flash.a), c.a);
+}"#;
+
+const DISSOLVE: &str = r#"#version 100
+precision mediump float;
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D tex;
+uniform float amount;
+uniform vec4 edge;
+
+void main() {
+    vec4 c = texture2D(tex, uv) * color;
+    float n = fract(sin(dot(floor(gl_FragCoord.xy / 4.0), vec2(12.9898, 78.233))) * 43758.5453);
+
+    if (n < amount) {
+        discard;
+    }
+
+    gl_FragColor = amount > 0.0 && n < amount + 0.05 ? vec4(edge.rgb, c.a * edge.a) : c;
+}"#;
+
+// Степень затухания в альфе цвета может быть больше 1, поэтому без lowp
+const LIGHT_VERTEX: &str = r#"#version 100
+attribute vec3 in_pos;
+attribute vec4 in_color;
+attribute vec2 in_uv;
+
+varying mediump vec4 color;
+varying mediump vec2 uv;
+
+uniform mat4 mvp;
+
+void main() {
+    gl_Position = mvp * vec4(in_pos, 1);
+    color = in_color;
+    uv = in_uv;
+}"#;
+
+const LIGHT: &str = r#"#version 100
+precision mediump float;
+varying mediump vec4 color;
+varying mediump vec2 uv;
+
+uniform sampler2D tex;
+
+void main() {
+    float a = pow(max(1.0 - length(uv), 0.0), color.a);
+    gl_FragColor = vec4(color.rgb * a, 1.0);
+}"#;
+
+const LIGHT_METAL: &str = r#"
+#include <metal_stdlib>
+
+using namespace metal;
+
+struct Uniforms
+{
+    float4x4 mvp;
+    float time;
+};
+
+struct Vertex
+{
+    float3 in_pos   [[attribute(0)]];
+    float4 in_color [[attribute(1)]];
+    float2 in_uv    [[attribute(2)]];
+};
+
+struct RasterizerData
+{
+    float4 position [[position]];
+    float4 color [[user(locn0)]];
+    float2 uv [[user(locn1)]];
+};
+
+vertex RasterizerData vertexShader(Vertex v [[stage_in]], constant Uniforms& uniforms [[buffer(0)]])
+{
+    RasterizerData out;
+
+    out.position = uniforms.mvp * float4(v.in_pos, 1.0);
+    out.color = v.in_color;
+    out.uv = v.in_uv;
+
+    return out;
+}
+
+fragment float4 fragmentShader(RasterizerData in [[stage_in]])
+{
+    float a = pow(max(1.0 - length(in.uv), 0.0), in.color.a);
+    return float4(in.color.rgb * a, 1.0);
+}"#;
@@ -1,6 +1,8 @@
 use super::{
-    d2::{upd_proj, CAMERA2D, CANVAS_UPDATE, MOUSE_PROJ, PROJ, RENDERS},
-    DELTA, LAST_FPS_TIME, LAST_FRAME_TIME, TEXUTRES_BUFFER, TEXUTRES_UPDATE, WINDOW, WINDOW_UPDATE, Texture,
+    atlas::TEXUTRES_PART,
+    material::{Material, Uniform, SHADERS},
+    d2::{screen_to_world, upd_proj, Redraw, CANVAS_UPDATE, PART, PROJ, RENDERS, TARGETS},
+    alpha_update, Blend, DELTA, Filter, Wrap, TEXUTRES_FILTER, TEXUTRES_WRAP, LAST_FPS_TIME, LAST_FRAME_TIME, START_TIME, TEXUTRES_BUFFER, TEXUTRES_UPDATE, WINDOW, WINDOW_UPDATE, Texture,
 };
 use crate::{
     engine::{
@@ -12,15 +14,122 @@ use crate::{
     object::Touch, render::Vertex,
 };
 
-use glam::{vec2, Vec2};
+use glam::{vec2, Mat4, Vec2};
 use miniquad::{window::set_window_size, *};
-use std::{usize, vec};
+use std::{collections::HashMap, usize, vec};
 
 static mut TEXUTRES: Vec<Option<TextureId>> = Vec::new();
+/// Контекст окна для чтения текстур вне кадра.
+static mut CONTEXT: Option<*mut dyn RenderingBackend> = None;
+
+/// Пачка узлов с общей текстурой и её буферы на GPU.
+struct Batch {
+    bindings: Bindings,
+    /// От какого куска какого узла и до какого.
+    range: ((usize, usize), (usize, usize)),
+    items: usize,
+    vertex_size: usize,
+    index_size: usize,
+    count: i32,
+}
+impl Batch {
+    fn new(ctx: &mut dyn RenderingBackend, white: TextureId) -> Self {
+        let (vertex_size, index_size) = (1024, 1536);
+
+        Self {
+            bindings: Bindings {
+                vertex_buffers: vec![ctx.new_buffer(
+                    BufferType::VertexBuffer,
+                    BufferUsage::Dynamic,
+                    BufferSource::empty::<Vertex>(vertex_size),
+                )],
+                index_buffer: ctx.new_buffer(
+                    BufferType::IndexBuffer,
+                    BufferUsage::Dynamic,
+                    BufferSource::empty::<u16>(index_size),
+                ),
+                images: vec![white],
+            },
+            range: ((usize::MAX, 0), (usize::MAX, 0)),
+            items: 0,
+            vertex_size,
+            index_size,
+            count: 0,
+        }
+    }
+
+    fn upload(&mut self, ctx: &mut dyn RenderingBackend, verts: &[Vertex], indis: &[u16]) {
+        // Буферы растут, если геометрия пачки перестала влезать
+        if verts.len() > self.vertex_size {
+            self.vertex_size = verts.len().next_power_of_two();
+            ctx.delete_buffer(self.bindings.vertex_buffers[0]);
+            self.bindings.vertex_buffers[0] = ctx.new_buffer(
+                BufferType::VertexBuffer,
+                BufferUsage::Dynamic,
+                BufferSource::empty::<Vertex>(self.vertex_size),
+            );
+        }
+
+        if indis.len() > self.index_size {
+            self.index_size = indis.len().next_power_of_two();
+            ctx.delete_buffer(self.bindings.index_buffer);
+            self.bindings.index_buffer = ctx.new_buffer(
+                BufferType::IndexBuffer,
+                BufferUsage::Dynamic,
+                BufferSource::empty::<u16>(self.index_size),
+            );
+        }
+
+        if !verts.is_empty() {
+            ctx.buffer_update(self.bindings.vertex_buffers[0], BufferSource::slice(verts));
+            ctx.buffer_update(self.bindings.index_buffer, BufferSource::slice(indis));
+        }
+
+        self.count = indis.len() as i32;
+    }
+}
+
+/// Конвейер с общим форматом вершин и смешиванием `blend`.
+fn new_pipeline(ctx: &mut dyn RenderingBackend, shader: ShaderId, blend: Blend) -> Pipeline {
+    use BlendFactor::{One, OneMinusValue, Value, Zero};
+    use BlendValue::{DestinationColor, SourceAlpha, SourceColor};
+
+    // Альфа приёмника накапливается, а не остаётся прежней
+    let (color, alpha) = match blend {
+        Blend::Alpha => ((Value(SourceAlpha), OneMinusValue(SourceAlpha)), (One, OneMinusValue(SourceAlpha))),
+        Blend::Additive => ((Value(SourceAlpha), One), (Zero, One)),
+        // Цвет уже умножен на альфу: приёмник * lerp(1, цвет, альфа)
+        Blend::Multiply => ((Value(DestinationColor), OneMinusValue(SourceAlpha)), (Zero, One)),
+        Blend::Screen => ((One, OneMinusValue(SourceColor)), (One, OneMinusValue(SourceAlpha))),
+        Blend::Premultiplied => ((One, OneMinusValue(SourceAlpha)), (One, OneMinusValue(SourceAlpha))),
+    };
+
+    ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[
+            VertexAttribute::new("in_pos", VertexFormat::Float3),
+            VertexAttribute::new("in_color", VertexFormat::Float4),
+            VertexAttribute::new("in_uv", VertexFormat::Float2),
+        ],
+        shader,
+        PipelineParams {
+            color_blend: Some(BlendState::new(Equation::Add, color.0, color.1)),
+            alpha_blend: Some(BlendState::new(Equation::Add, alpha.0, alpha.1)),
+            ..Default::default()
+        },
+    )
+}
 
 struct QuadRender {
-    pipeline: Pipeline,
-    bindings: Vec<Bindings>,
+    shader: ShaderId,
+    multiply: ShaderId,
+    /// Шейдеры материалов по id, `Some(None)` - не собрался.
+    materials: Vec<Option<Option<ShaderId>>>,
+    /// Конвейеры по шейдеру материала (`None` - обычный) и смешиванию.
+    pipelines: HashMap<(Option<usize>, Blend), Pipeline>,
+    /// Пачки экрана (0) и каждой цели рендера (id + 1).
+    batches: Vec<Vec<Batch>>,
+    passes: Vec<Option<RenderPass>>,
     ctx: Box<dyn RenderingBackend>,
     white: TextureId,
 }
@@ -39,57 +148,276 @@ impl QuadRender {
 
         let white = ctx.new_texture_from_rgba8(1, 1, &[0xFF, 0xFF, 0xFF, 0xFF]);
 
-        let shader = ctx
-            .new_shader(
+        let mut new_shader = |fragment: &str, metal: &str| {
+            let program = format!("{}{}", shader::METAL, metal);
+
+            ctx.new_shader(
                 match ctx.info().backend {
                     Backend::OpenGl => ShaderSource::Glsl {
                         vertex: shader::VERTEX,
-                        fragment: shader::FRAGMENT,
-                    },
-                    Backend::Metal => ShaderSource::Msl {
-                        program: shader::METAL,
+                        fragment,
                     },
+                    Backend::Metal => ShaderSource::Msl { program: &program },
                 },
                 shader::meta(),
             )
-            .expect("Error to load shaders");
-
-        let pipeline = ctx.new_pipeline(
-            &[BufferLayout::default()],
-            &[
-                VertexAttribute::new("in_pos", VertexFormat::Float3),
-                VertexAttribute::new("in_color", VertexFormat::Float4),
-                VertexAttribute::new("in_uv", VertexFormat::Float2),
-            ],
-            shader,
-            PipelineParams {
-                color_blend: Some(BlendState::new(
-                    Equation::Add,
-                    BlendFactor::Value(BlendValue::SourceAlpha),
-                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
-                )),
-                alpha_blend: Some(BlendState::new(
-                    Equation::Add,
-                    BlendFactor::Zero,
-                    BlendFactor::One,
-                )),
-                ..Default::default()
-            },
-        );
+            .expect("Error to load shaders")
+        };
+
+        let shader = new_shader(shader::FRAGMENT, shader::METAL_FRAGMENT);
+        // Для `Blend::Multiply` цвет умножается на альфу ещё в шейдере
+        let multiply = new_shader(shader::MULTIPLY, shader::METAL_MULTIPLY);
 
         unsafe {
             LAST_FRAME_TIME = date::now();
-            LAST_FPS_TIME = LAST_FRAME_TIME + 1.
+            LAST_FPS_TIME = LAST_FRAME_TIME + 1.;
+            START_TIME = LAST_FRAME_TIME;
+            CONTEXT = Some(ctx.as_mut() as *mut dyn RenderingBackend);
         }
 
         Self {
-            pipeline,
-            bindings: Vec::new(),
+            shader,
+            multiply,
+            materials: Vec::new(),
+            pipelines: HashMap::new(),
+            batches: Vec::new(),
+            passes: Vec::new(),
             ctx,
             white,
         }
     }
 }
+impl QuadRender {
+    /// Рисует пачки узлов одной цели (`None` - экран) с её проекцией.
+    fn draw_batches(&mut self, target: Option<usize>, proj: Mat4) {
+        let time = unsafe { (date::now() - START_TIME) as f32 };
+        let mut current: Option<Pipeline> = None;
+
+        let mut verts: Vec<Vertex> = vec![];
+        let mut indis: Vec<u16> = vec![];
+
+        let renders = unsafe { &mut RENDERS };
+        let list = target.map_or(0, |id| id + 1);
+
+        if self.batches.len() <= list {
+            self.batches.resize_with(list + 1, Vec::new);
+        }
+
+        let mut batch_num: usize = 0;
+        let mut i: usize = 0;
+        let mut part: usize = 0;
+
+        // Подряд идущие узлы с одной текстурой, материалом и смешиванием склеиваются в одну пачку.
+        // Пачка пересобирается, только если поменялся её состав или узлы в ней.
+        // Узел, который не влез в индексы u16, делится между пачками по кускам.
+        while i < renders.len() {
+            let start = (i, part);
+            let mut texture: Option<Option<usize>> = None;
+            let mut material: Option<Material> = None;
+            let mut blend = Blend::Alpha;
+            let mut changed = false;
+            let mut size: usize = 0;
+            let mut items: usize = 0;
+
+            'batch: while i < renders.len() {
+                if let Some(obj) = renders[i].as_ref().filter(|obj| obj.target == target) {
+                    if texture.is_some_and(|t| t != obj.texture)
+                        || (texture.is_some() && (material != obj.material || blend != obj.blend))
+                    {
+                        break;
+                    }
+
+                    if texture.is_none() {
+                        material = obj.material.clone();
+                        blend = obj.blend;
+                    }
+                    texture = Some(obj.texture);
+                    changed |= obj.update != DrawUpdate::None;
+                    items += 1;
+
+                    for (vertices, _) in obj.parts().skip(part) {
+                        if size > 0 && size + vertices.len() > PART {
+                            break 'batch;
+                        }
+                        size += vertices.len();
+                        part += 1;
+                    }
+                }
+                i += 1;
+                part = 0;
+            }
+
+            let texture = match texture {
+                Some(texture) => texture,
+                None => continue,
+            };
+
+            // Материал, не собравшийся на этом бэкенде, рисуется обычным шейдером
+            let custom = material.as_ref().and_then(|m| self.pipeline(Some(m.shader), blend));
+            let pipeline = self.pipeline(None, blend).unwrap();
+
+            if batch_num == self.batches[list].len() {
+                self.batches[list].push(Batch::new(self.ctx.as_mut(), self.white));
+            }
+
+            let batch = &mut self.batches[list][batch_num];
+
+            let end = (i, part);
+
+            if changed || batch.range != (start, end) || batch.items != items {
+                verts.clear();
+                indis.clear();
+
+                for (k, obj) in renders.iter().enumerate().take(end.0 + 1).skip(start.0) {
+                    let Some(obj) = obj.as_ref().filter(|obj| obj.target == target) else {
+                        continue;
+                    };
+                    let from = if k == start.0 { start.1 } else { 0 };
+                    let to = if k == end.0 { end.1 } else { usize::MAX };
+
+                    for (vertices, indices) in obj.parts().take(to).skip(from) {
+                        let base_index = verts.len() as u16;
+
+                        verts.extend_from_slice(vertices);
+                        // Смещаем индексы на количество уже имеющихся вершин
+                        indis.extend(indices.iter().map(|index| index + base_index));
+                    }
+                }
+
+                batch.range = (start, end);
+                batch.items = items;
+                batch.upload(self.ctx.as_mut(), &verts, &indis);
+            }
+
+            let image = |id: Option<usize>| {
+                id.and_then(|id| unsafe { TEXUTRES.get(id).copied().flatten() })
+                    .unwrap_or(self.white)
+            };
+
+            batch.bindings.images.truncate(1);
+            batch.bindings.images[0] = image(texture);
+
+            if batch.count > 0 {
+                match (custom, &material) {
+                    (Some(custom), Some(material)) => {
+                        batch
+                            .bindings
+                            .images
+                            .extend(material.images.iter().map(|id| image(*id)));
+
+                        // mvp, time и переменные материала подряд, как в ShaderMeta
+                        let mut uniforms = proj.to_cols_array().to_vec();
+                        uniforms.push(time);
+                        for u in &material.uniforms {
+                            uniforms.extend(u.floats());
+                        }
+
+                        self.ctx.apply_pipeline(&custom);
+                        self.ctx.apply_bindings(&batch.bindings);
+                        self.ctx.apply_uniforms_from_bytes(
+                            uniforms.as_ptr() as *const u8,
+                            uniforms.len() * 4,
+                        );
+                        current = Some(custom);
+                    }
+                    _ => {
+                        if current != Some(pipeline) {
+                            current = Some(pipeline);
+                            self.ctx.apply_pipeline(&pipeline);
+                            self.ctx
+                                .apply_uniforms(UniformsSource::table(&shader::Uniforms { mvp: proj }));
+                        }
+                        self.ctx.apply_bindings(&batch.bindings);
+                    }
+                }
+
+                self.ctx.draw(0, batch.count, 1);
+            }
+
+            batch_num += 1;
+        }
+
+        // Куски одного узла могут лежать в разных пачках, поэтому сброс после всех
+        for obj in renders.iter_mut().flatten().filter(|obj| obj.target == target) {
+            obj.update = DrawUpdate::None;
+        }
+    }
+}
+impl QuadRender {
+    /// Конвейер из кэша, собирается при первом использовании.
+    /// `None`, если шейдер материала не собрался.
+    fn pipeline(&mut self, material: Option<usize>, blend: Blend) -> Option<Pipeline> {
+        if let Some(pipeline) = self.pipelines.get(&(material, blend)) {
+            return Some(*pipeline);
+        }
+
+        let shader = match material {
+            Some(id) => self.material_shader(id)?,
+            None if blend == Blend::Multiply => self.multiply,
+            None => self.shader,
+        };
+
+        let pipeline = new_pipeline(self.ctx.as_mut(), shader, blend);
+        self.pipelines.insert((material, blend), pipeline);
+        Some(pipeline)
+    }
+
+    fn material_shader(&mut self, id: usize) -> Option<ShaderId> {
+        if self.materials.len() <= id {
+            self.materials.resize(id + 1, None);
+        }
+
+        if let Some(shader) = self.materials[id] {
+            return shader;
+        }
+
+        let shader = unsafe { &SHADERS[id] };
+
+        let source = match self.ctx.info().backend {
+            Backend::OpenGl => Some(ShaderSource::Glsl {
+                vertex: shader.vertex.as_deref().unwrap_or(shader::VERTEX),
+                fragment: &shader.fragment,
+            }),
+            Backend::Metal => shader
+                .metal
+                .as_deref()
+                .map(|program| ShaderSource::Msl { program }),
+        };
+
+        let mut uniforms = vec![
+            UniformDesc::new("mvp", UniformType::Mat4),
+            UniformDesc::new("time", UniformType::Float1),
+        ];
+        uniforms.extend(shader.uniforms.iter().map(|(name, value)| {
+            let kind = match value {
+                Uniform::Float(_) => UniformType::Float1,
+                Uniform::Vec2(_) => UniformType::Float2,
+                Uniform::Vec3(_) => UniformType::Float3,
+                Uniform::Vec4(_) => UniformType::Float4,
+            };
+            UniformDesc::new(name, kind)
+        }));
+
+        let mut images = vec!["tex".to_string()];
+        images.extend(shader.images.iter().cloned());
+
+        // Ошибка остаётся у материала (`Material::error`), узел рисуется без него
+        let shader = source.and_then(|source| {
+            match self.ctx.new_shader(source, ShaderMeta { images, uniforms: UniformBlockLayout { uniforms } }) {
+                Ok(shader) => Some(shader),
+                Err(err) => {
+                    unsafe {
+                        SHADERS[id].error = Some(format!("{err:?}"));
+                    }
+                    None
+                }
+            }
+        });
+
+        self.materials[id] = Some(shader);
+        shader
+    }
+}
 impl EventHandler for QuadRender {
     fn update(&mut self) {
         let u = std::time::Instant::now();
@@ -109,24 +437,35 @@ impl EventHandler for QuadRender {
         draw();
         println!("vertex: {:?}", u.elapsed());
 
-        unsafe {
-            for i in &TEXUTRES_BUFFER {
-                TEXUTRES.push(Some(self.ctx.new_texture_from_rgba8(i.1, i.2, &i.0)));
+        upload(self.ctx.as_mut());
+
+        let u = std::time::Instant::now();
+
+        // Сначала цели рендера, их текстуры нужны экрану в этом же кадре
+        for id in 0..unsafe { TARGETS.len() } {
+            let target = unsafe { &mut TARGETS[id] };
+
+            if !target.dirty && target.redraw != Redraw::Always {
+                continue;
             }
 
-            TEXUTRES_BUFFER.clear();
+            let Some(texture) = (unsafe { TEXUTRES.get(target.texture).copied().flatten() }) else {
+                continue;
+            };
 
-            for i in &TEXUTRES_UPDATE {
-                if let Some(tex) = TEXUTRES[i.0] {
-                    self.ctx
-                        .texture_resize(tex, i.2 as u32, i.3 as u32, Some(&i.1));
-                    //self.ctx.texture_update_part(tex, 0, 0, i.2 as i32, i.3 as i32, &i.1);
-                }
+            if self.passes.len() <= id {
+                self.passes.resize(id + 1, None);
             }
-            TEXUTRES_UPDATE.clear();
-        }
+            let pass = *self.passes[id].get_or_insert_with(|| self.ctx.new_render_pass(texture, None));
 
-        let u = std::time::Instant::now();
+            let c = target.clear;
+            self.ctx.begin_pass(Some(pass), PassAction::clear_color(c.r, c.g, c.b, c.a));
+            self.draw_batches(Some(id), target.proj());
+            self.ctx.end_render_pass();
+
+            target.dirty = false;
+            alpha_update(target.texture);
+        }
 
         //self.ctx.clear(Some((backgraund.r, backgraund.g, backgraund.b, backgraund.a)), None, None);
         unsafe {
@@ -138,109 +477,7 @@ impl EventHandler for QuadRender {
             ));
         }
 
-        self.ctx.apply_pipeline(&self.pipeline);
-        self.ctx
-            .apply_uniforms(UniformsSource::table(&shader::Uniforms {
-                mvp: unsafe { PROJ },
-            }));
-        
-        let mut verts: Vec<Vertex> = vec![];
-        let mut indis: Vec<u16> = vec![];
-        let mut last_texture: Option<usize> = Some(usize::MAX);
-        let mut new_texture: Option<usize> = last_texture;
-        let mut last_new_render = DrawUpdate::None;
-
-        let mut bind_num: usize = 0;
-        
-        for i in unsafe { RENDERS.iter_mut().enumerate() } {
-            if let None = i.1 {
-                continue;
-            }
-
-            let obj = i.1.as_mut().unwrap();
-
-            last_new_render = if obj.3 == DrawUpdate::Create || last_new_render == DrawUpdate::Create {
-                DrawUpdate::Create
-            } else if obj.3 == DrawUpdate::Update || last_new_render == DrawUpdate::Update {
-                DrawUpdate::Update
-            } else {
-                DrawUpdate::None
-            };
-            obj.3 = DrawUpdate::None;
-
-            new_texture = obj.2.clone();
-
-            if (new_texture != last_texture && i.0 != 0 ) || i.0 == unsafe { RENDERS.len() - 1 } {
-                if last_new_render == DrawUpdate::Create {
-                    let vertex_buffer = self.ctx.new_buffer(
-                        BufferType::VertexBuffer,
-                        BufferUsage::Dynamic,
-                        BufferSource::slice(&verts),
-                    );
-
-                    let index_buffer = self.ctx.new_buffer(
-                        BufferType::IndexBuffer,
-                        BufferUsage::Dynamic,
-                        BufferSource::slice(&indis),
-                    );
-
-                    let images = if let Some(id) = last_texture {
-                        unsafe {
-                            if let Some(tex) = TEXUTRES[id] {
-                                tex
-                            } else {
-                                self.white
-                            }
-                        }
-                    } else {
-                        self.white
-                    };
-
-                    let bindings = Bindings {
-                        vertex_buffers: vec![vertex_buffer],
-                        index_buffer,
-                        images: vec![images],
-                    };
-
-                    self.bindings.push(bindings);
-                } else if last_new_render == DrawUpdate::Update {
-                    self.ctx.buffer_update(
-                        self.bindings[bind_num].vertex_buffers[0],
-                        BufferSource::slice(&verts),
-                    );
-                    self.ctx.buffer_update(
-                        self.bindings[bind_num].index_buffer,
-                        BufferSource::slice(&indis),
-                    );
-                }
-
-                self.ctx.apply_bindings(&self.bindings[bind_num]);
-                self.ctx.draw(0, indis.len() as i32, 1);
-
-                bind_num += 1;
-
-                last_new_render = DrawUpdate::None;
-            }
-
-            if new_texture != last_texture || i.0 == unsafe { RENDERS.len() - 1 } || i.0 == 0 {
-                verts = obj.0.clone();
-                indis = obj.1.clone();
-
-                last_texture = new_texture;
-            } else {
-                let base_index = verts.len() as u16;
-                let vert: Vec<Vertex> = obj.0.clone();
-                let mut indi = obj.1.clone();
-
-                // Смещаем индексы на количество уже имеющихся вершин
-                for index in &mut indi {
-                    *index += base_index;
-                }
-
-                verts.extend(vert);
-                indis.extend(indi);
-            }
-        }
+        self.draw_batches(None, unsafe { PROJ });
 
         self.ctx.end_render_pass();
 
@@ -333,6 +570,84 @@ impl EventHandler for QuadRender {
     }*/
 }
 
+/// Загружает на GPU новые текстуры и их изменения.
+fn upload(ctx: &mut dyn RenderingBackend) {
+    unsafe {
+        for i in &TEXUTRES_BUFFER {
+            // Пустой буфер - текстура без данных, её заполнят частичные обновления
+            let texture = if i.0.is_empty() {
+                ctx.new_texture(
+                    TextureAccess::Static,
+                    TextureSource::Empty,
+                    TextureParams {
+                        width: i.1 as u32,
+                        height: i.2 as u32,
+                        format: TextureFormat::RGBA8,
+                        ..Default::default()
+                    },
+                )
+            } else {
+                ctx.new_texture_from_rgba8(i.1, i.2, &i.0)
+            };
+            TEXUTRES.push(Some(texture));
+        }
+
+        TEXUTRES_BUFFER.clear();
+
+        for i in &TEXUTRES_UPDATE {
+            if let Some(tex) = TEXUTRES[i.0] {
+                ctx.texture_resize(tex, i.2 as u32, i.3 as u32, Some(&i.1));
+                //ctx.texture_update_part(tex, 0, 0, i.2 as i32, i.3 as i32, &i.1);
+            }
+        }
+        TEXUTRES_UPDATE.clear();
+
+        for i in &TEXUTRES_PART {
+            if let Some(tex) = TEXUTRES[i.0] {
+                ctx.texture_update_part(tex, i.1 as i32, i.2 as i32, i.3 as i32, i.4 as i32, &i.5);
+            }
+        }
+        TEXUTRES_PART.clear();
+
+        for (id, wrap) in &TEXUTRES_WRAP {
+            if let Some(tex) = TEXUTRES[*id] {
+                let wrap = match wrap {
+                    Wrap::Repeat => TextureWrap::Repeat,
+                    Wrap::Mirror => TextureWrap::Mirror,
+                    Wrap::Clamp => TextureWrap::Clamp,
+                };
+                ctx.texture_set_wrap(tex, wrap, wrap);
+            }
+        }
+        TEXUTRES_WRAP.clear();
+
+        for (id, filter) in &TEXUTRES_FILTER {
+            if let Some(tex) = TEXUTRES[*id] {
+                let filter = match filter {
+                    Filter::Linear => FilterMode::Linear,
+                    Filter::Nearest => FilterMode::Nearest,
+                };
+                ctx.texture_set_filter(tex, filter, MipmapFilterMode::None);
+            }
+        }
+        TEXUTRES_FILTER.clear();
+    }
+}
+
+/// Пиксели текстуры с GPU вместе с ещё не загруженными изменениями.
+/// `None`, пока окно не создано.
+pub(crate) fn read_texture(id: usize) -> Option<(Vec<u8>, u32, u32)> {
+    let ctx = unsafe { &mut *CONTEXT? };
+    upload(ctx);
+
+    let texture = unsafe { TEXUTRES.get(id).copied().flatten()? };
+    let (width, height) = ctx.texture_size(texture);
+
+    let mut rgba = vec![0; (width * height * 4) as usize];
+    ctx.texture_read_pixels(texture, &mut rgba);
+    Some((rgba, width, height))
+}
+
 pub(crate) fn render(name: &str) {
     unsafe {
         let conf = conf::Conf {
@@ -356,8 +671,7 @@ pub(crate) fn render(name: &str) {
 
 #[inline(always)]
 fn get_mouse_proj(x: f32, y: f32) -> Vec2 {
-    //y - half_window.y) * get_mouse_proj().y - get_camera().y,
-    unsafe { (vec2(x, y) - WINDOW / 2.) * MOUSE_PROJ + CAMERA2D }
+    screen_to_world(vec2(x, y))
 }
 
 mod shader {
@@ -389,6 +703,17 @@ mod shader {
         gl_FragColor = texture2D(tex, uv) * color;
     }"#;
 
+    pub const MULTIPLY: &str = r#"#version 100
+    varying lowp vec4 color;
+    varying lowp vec2 uv;
+
+    uniform sampler2D tex;
+
+    void main() {
+        lowp vec4 c = texture2D(tex, uv) * color;
+        gl_FragColor = vec4(c.rgb * c.a, c.a);
+    }"#;
+
     pub const METAL: &str = r#"
     #include <metal_stdlib>
 
@@ -417,13 +742,21 @@ mod shader {
         out.uv = v.in_uv;
 
         return out;
-    }
+    }"#;
 
+    pub const METAL_FRAGMENT: &str = r#"
     fragment float4 fragmentShader(RasterizerData in [[stage_in]], texture2d<float> tex [[texture(0)]], sampler texSmplr [[sampler(0)]])
     {
         return in.color * tex.sample(texSmplr, in.uv);
     }"#;
 
+    pub const METAL_MULTIPLY: &str = r#"
+    fragment float4 fragmentShader(RasterizerData in [[stage_in]], texture2d<float> tex [[texture(0)]], sampler texSmplr [[sampler(0)]])
+    {
+        float4 c = in.color * tex.sample(texSmplr, in.uv);
+        return float4(c.rgb * c.a, c.a);
+    }"#;
+
     pub fn meta() -> ShaderMeta {
         ShaderMeta {
             images: vec!["tex".to_string()],
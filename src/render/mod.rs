@@ -1,5 +1,9 @@
+pub mod atlas;
 pub mod d2;
 pub mod d3;
+pub mod light;
+pub mod material;
+pub mod shape;
 
 #[cfg(feature = "miniquad")]
 pub mod miniquad;
@@ -9,6 +13,12 @@ pub mod wgpu;
 
 use crate::data::load_file;
 
+#[cfg(feature = "miniquad")]
+use self::miniquad::read_texture;
+
+#[cfg(feature = "wgpu")]
+use self::wgpu::read_texture;
+
 use glam::{Vec2, Vec3};
 use image::{load_from_memory, DynamicImage, GenericImageView};
 use rusttype::{point, Font as RFont, Scale};
@@ -18,6 +28,7 @@ pub(crate) static mut WINDOW_UPDATE: bool = false;
 
 pub(super) static mut DELTA: f32 = 0.;
 pub(super) static mut LAST_FRAME_TIME: f64 = 0.;
+pub(super) static mut START_TIME: f64 = 0.;
 
 pub(super) static mut FPS: u16 = 60;
 pub(super) static mut FPS_BUFFER: u16 = 0;
@@ -38,7 +49,23 @@ pub enum View {
     Window,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Смешивание цвета узла с тем, что уже нарисовано. Поддерживается бэкендом
+/// miniquad, wgpu узлы пока не рисует.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Blend {
+    #[default]
+    Alpha,
+    /// Свечение, частицы.
+    Additive,
+    /// Тени, затемнение. Шейдер материала должен отдавать цвет,
+    /// умноженный на альфу, как это делает обычный.
+    Multiply,
+    Screen,
+    /// Для цвета, уже умноженного на альфу, например текстур целей рендера.
+    Premultiplied,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Rgba {
     pub r: f32,
     pub g: f32,
@@ -122,30 +149,209 @@ pub fn font(path: &str) -> Font {
 pub(crate) static mut TEXUTRES_ID: usize = 0;
 pub(crate) static mut TEXUTRES_BUFFER: Vec<(Vec<u8>, u16, u16)> = Vec::new();
 pub(crate) static mut TEXUTRES_UPDATE: Vec<(usize, Vec<u8>, u16, u16)> = Vec::new();
+/// Альфа-канал текстур на CPU для попадания по пикселям.
+/// Читается с GPU при первой проверке и сбрасывается при изменении текстуры.
+static mut TEXUTRES_ALPHA: Vec<Option<(Vec<u8>, u16, u16)>> = Vec::new();
+
+/// UV всей текстуры.
+pub(crate) const FULL_UV: [f32; 4] = [0., 0., 1., 1.];
+
+/// Повтор текстуры за пределами uv 0..1.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Wrap {
+    Repeat,
+    Mirror,
+    Clamp,
+}
+
+/// Сглаживание: Nearest для пиксель-арта.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Filter {
+    Linear,
+    Nearest,
+}
+
+pub(crate) static mut TEXUTRES_WRAP: Vec<(usize, Wrap)> = Vec::new();
+pub(crate) static mut TEXUTRES_FILTER: Vec<(usize, Filter)> = Vec::new();
+/// Заданные режимы текстур, повторный вызов с тем же значением ничего не шлёт.
+static mut WRAPS: Vec<(usize, Wrap)> = Vec::new();
+static mut FILTERS: Vec<(usize, Filter)> = Vec::new();
 
 #[derive(Clone, Copy, PartialEq)]
 pub struct Texture {
     pub(crate) id: usize,
     pub width: f32,
     pub height: f32,
+    /// Область в странице атласа: (u0, v0, u1, v1).
+    pub(crate) uv: [f32; 4],
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// Сдвиг и масштаб uv внутри области, для прокрутки фона вместе с `Wrap::Repeat`.
+    pub uv_offset: Vec2,
+    pub uv_scale: Vec2,
+}
+
+impl Texture {
+    pub(crate) fn new(id: usize, width: f32, height: f32, uv: [f32; 4]) -> Self {
+        Self {
+            id,
+            width,
+            height,
+            uv,
+            flip_x: false,
+            flip_y: false,
+            uv_offset: Vec2::ZERO,
+            uv_scale: Vec2::ONE,
+        }
+    }
+
+    /// Часть текстуры в пикселях: (x, y, ширина, высота).
+    pub fn region(&self, x: f32, y: f32, w: f32, h: f32) -> Texture {
+        let size = Vec2::new(self.width, self.height);
+        let min = self.rect_uv(Vec2::new(x, y) / size);
+        let max = self.rect_uv(Vec2::new(x + w, y + h) / size);
+
+        Texture {
+            width: w,
+            height: h,
+            uv: [min.x, min.y, max.x, max.y],
+            ..*self
+        }
+    }
+
+    pub fn flip(mut self, x: bool, y: bool) -> Self {
+        self.flip_x = x;
+        self.flip_y = y;
+        self
+    }
+
+    pub fn uv_offset(mut self, x: f32, y: f32) -> Self {
+        self.uv_offset = Vec2::new(x, y);
+        self
+    }
+
+    pub fn uv_scale(mut self, x: f32, y: f32) -> Self {
+        self.uv_scale = Vec2::new(x, y);
+        self
+    }
+
+    /// Режим повтора. Режим задаётся всей странице, поэтому текстура из атласа
+    /// переносится в отдельную, одну на область: `wrap` и `filter` вместе
+    /// действуют на одну копию.
+    pub fn wrap(self, wrap: Wrap) -> Self {
+        let texture = self.unpacked();
+        unsafe {
+            if changed(&mut WRAPS, texture.id, wrap) {
+                TEXUTRES_WRAP.push((texture.id, wrap));
+            }
+        }
+        texture
+    }
+
+    /// Режим сглаживания. Текстура из атласа переносится в отдельную, как в `wrap`.
+    pub fn filter(self, filter: Filter) -> Self {
+        let texture = self.unpacked();
+        unsafe {
+            if changed(&mut FILTERS, texture.id, filter) {
+                TEXUTRES_FILTER.push((texture.id, filter));
+            }
+        }
+        texture
+    }
+
+    /// Копирует отражение и uv-настройки из `other`.
+    pub(crate) fn flags(self, other: &Texture) -> Self {
+        Texture {
+            id: self.id,
+            width: self.width,
+            height: self.height,
+            uv: self.uv,
+            ..*other
+        }
+    }
+
+    /// Отдельная копия области атласа, одна на область: повторные вызовы
+    /// отдают ту же текстуру, пока место в атласе не перезаписано.
+    pub(crate) fn unpacked(self) -> Self {
+        match atlas::unpacked(&self) {
+            Some(id) => Texture { id, uv: FULL_UV, ..self },
+            None => self,
+        }
+    }
+
+    /// Переводит uv области (0..1) в uv страницы, без отражения и сдвига.
+    #[inline(always)]
+    fn rect_uv(&self, uv: Vec2) -> Vec2 {
+        Vec2::new(
+            self.uv[0] + (self.uv[2] - self.uv[0]) * uv.x,
+            self.uv[1] + (self.uv[3] - self.uv[1]) * uv.y,
+        )
+    }
+
+    /// Переводит uv текстуры (0..1) в uv страницы атласа.
+    #[inline(always)]
+    pub(crate) fn uv(&self, uv: Vec2) -> Vec2 {
+        let mut uv = uv;
+        if self.flip_x {
+            uv.x = 1. - uv.x;
+        }
+        if self.flip_y {
+            uv.y = 1. - uv.y;
+        }
+
+        self.rect_uv(self.uv_offset + uv * self.uv_scale)
+    }
+}
+
+/// Запоминает режим текстуры, `true` если он изменился.
+fn changed<T: PartialEq>(modes: &mut Vec<(usize, T)>, id: usize, value: T) -> bool {
+    match modes.iter_mut().find(|m| m.0 == id) {
+        Some(m) if m.1 == value => false,
+        Some(m) => {
+            m.1 = value;
+            true
+        }
+        None => {
+            modes.push((id, value));
+            true
+        }
+    }
 }
 
 #[inline(always)]
 pub fn texture(path: &str) -> Texture {
+    let (rgba, width, height) = load_image(path);
+    new_texture(rgba, width, height)
+}
+
+#[inline(always)]
+pub(crate) fn load_image(path: &str) -> (Vec<u8>, u32, u32) {
     let img = load_from_memory(&load_file(path).expect("Error to loading texture"))
         .expect("Error to convert bytes from image");
     //let img = image::open(path).expect("Error to load texture");
 
-    let rgba = img.to_rgba8().to_vec();
     let (width, height) = img.dimensions();
+    (img.to_rgba8().to_vec(), width, height)
+}
 
-    let (id, w, h) = add_texture_buffer(rgba, width, height);
+/// Как `texture`, но с ошибкой вместо паники.
+pub(crate) fn try_texture(path: &str) -> Result<Texture, String> {
+    let bytes = load_file(path).map_err(|e| format!("{path}: {e}"))?;
+    let img = load_from_memory(&bytes).map_err(|e| format!("{path}: {e}"))?;
+
+    let (width, height) = img.dimensions();
+    Ok(new_texture(img.to_rgba8().to_vec(), width, height))
+}
 
-    Texture {
-        id,
-        width: w,
-        height: h,
+/// Новая текстура: в атлас, если помещается, иначе отдельная.
+pub(crate) fn new_texture(rgba: Vec<u8>, width: u32, height: u32) -> Texture {
+    if let Some(texture) = atlas::pack(&rgba, width, height, false) {
+        return texture;
     }
+
+    let (id, w, h) = add_texture_buffer(rgba, width, height);
+
+    Texture::new(id, w, h, FULL_UV)
 }
 
 #[inline(always)]
@@ -158,18 +364,70 @@ fn add_texture_buffer(rgba: Vec<u8>, width: u32, height: u32) -> (usize, f32, f3
 }
 #[inline(always)]
 fn upd_texture_buffer(id: usize, rgba: Vec<u8>, width: u32, height: u32) -> (usize, f32, f32) {
+    alpha_update(id);
+
     unsafe {
         TEXUTRES_UPDATE.push((id, rgba, width as u16, height as u16));
         (id, width as f32, height as f32)
     }
 }
 
+#[inline(always)]
+fn alpha(rgba: &[u8]) -> Vec<u8> {
+    rgba.chunks_exact(4).map(|p| p[3]).collect()
+}
+
+/// Текстура изменилась: её альфа будет прочитана заново.
+#[inline(always)]
+pub(crate) fn alpha_update(id: usize) {
+    unsafe {
+        if let Some(alpha) = TEXUTRES_ALPHA.get_mut(id) {
+            *alpha = None;
+        }
+    }
+}
+
+fn alpha_mask(id: usize) -> Option<&'static (Vec<u8>, u16, u16)> {
+    unsafe {
+        if TEXUTRES_ALPHA.len() <= id {
+            TEXUTRES_ALPHA.resize(id + 1, None);
+        }
+
+        if TEXUTRES_ALPHA[id].is_none() {
+            let (rgba, width, height) = read_texture(id)?;
+            TEXUTRES_ALPHA[id] = Some((alpha(&rgba), width as u16, height as u16));
+        }
+
+        TEXUTRES_ALPHA[id].as_ref()
+    }
+}
+
+/// Альфа текстуры в точке `uv` (0..1), 0 за её пределами.
+pub(crate) fn texture_alpha(texture: &Texture, uv: Vec2) -> u8 {
+    if uv.x < 0. || uv.y < 0. || uv.x >= 1. || uv.y >= 1. {
+        return 0;
+    }
+
+    let uv = texture.uv(uv);
+
+    let (data, w, h) = match alpha_mask(texture.id) {
+        Some(alpha) => alpha,
+        None => return 0,
+    };
+
+    // Вне области только при Wrap::Repeat
+    let x = (uv.x.rem_euclid(1.) * *w as f32) as usize;
+    let y = (uv.y.rem_euclid(1.) * *h as f32) as usize;
+
+    data.get(y * *w as usize + x).copied().unwrap_or(0)
+}
+
 pub(crate) fn add_text(
     text: &str,
     size: f32,
     font_id: usize,
-    upd: Option<usize>,
-) -> (usize, f32, f32) {
+    upd: Option<&Texture>,
+) -> Texture {
     let scale = Scale::uniform(size);
 
     let font = unsafe { &FONTS[font_id] };
@@ -227,9 +485,23 @@ pub(crate) fn add_text(
         }
     }
 
-    if let Some(id) = upd {
-        upd_texture_buffer(id, image.to_vec(), width, height)
-    } else {
-        add_texture_buffer(image.to_vec(), width, height)
+    let rgba = image.to_vec();
+
+    let Some(&old) = upd else {
+        return new_texture(rgba, width, height);
+    };
+
+    // Текст остаётся на своём месте в атласе, пока помещается в него
+    if let Some(texture) = atlas::repack(&old, &rgba, width, height) {
+        return texture.flags(&old);
     }
+
+    // Текст вырос из места в атласе: место освобождается, текст пакуется заново
+    if atlas::is_page(old.id) {
+        atlas::free(&old);
+        return new_texture(rgba, width, height).flags(&old);
+    }
+
+    let (id, w, h) = upd_texture_buffer(old.id, rgba, width, height);
+    Texture::new(id, w, h, FULL_UV).flags(&old)
 }
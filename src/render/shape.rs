@@ -0,0 +1,469 @@
+use super::Rgba;
+
+use glam::{vec2, Vec2};
+use std::f32::consts::{PI, TAU};
+
+/// Соединение отрезков линии.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Join {
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// Концы незамкнутой линии.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Cap {
+    Butt,
+    Round,
+    Square,
+}
+
+/// Толщина и форма линии.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Line {
+    pub width: f32,
+    pub join: Join,
+    pub cap: Cap,
+}
+
+impl Line {
+    pub fn new(width: f32) -> Self {
+        Self {
+            width,
+            join: Join::Miter,
+            cap: Cap::Butt,
+        }
+    }
+
+    pub fn join(mut self, join: Join) -> Self {
+        self.join = join;
+        self
+    }
+
+    pub fn cap(mut self, cap: Cap) -> Self {
+        self.cap = cap;
+        self
+    }
+}
+
+/// Обводка фигуры своим цветом, по центру края.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Stroke {
+    pub width: f32,
+    pub color: Rgba,
+    pub join: Join,
+}
+
+impl Stroke {
+    pub fn new(width: f32, color: Rgba) -> Self {
+        Self {
+            width,
+            color,
+            join: Join::Miter,
+        }
+    }
+
+    pub fn join(mut self, join: Join) -> Self {
+        self.join = join;
+        self
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Spread {
+    Linear(Vec2, Vec2),
+    Radial(Vec2, f32),
+}
+
+/// Градиентная заливка фигуры. Точки в локальных координатах узла,
+/// цвет узла умножается на цвет градиента.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Gradient {
+    spread: Spread,
+    stops: Vec<(f32, Rgba)>,
+}
+
+impl Gradient {
+    /// Линейный градиент от (x1, y1) до (x2, y2).
+    pub fn linear(x1: f32, y1: f32, x2: f32, y2: f32) -> Self {
+        Self {
+            spread: Spread::Linear(vec2(x1, y1), vec2(x2, y2)),
+            stops: Vec::new(),
+        }
+    }
+
+    /// Радиальный градиент из центра (x, y) до радиуса `r`.
+    pub fn radial(x: f32, y: f32, r: f32) -> Self {
+        Self {
+            spread: Spread::Radial(vec2(x, y), r),
+            stops: Vec::new(),
+        }
+    }
+
+    /// Цвет в точке `t` от 0 до 1.
+    pub fn stop(mut self, t: f32, color: Rgba) -> Self {
+        let t = t.clamp(0., 1.);
+        let i = self.stops.partition_point(|(s, _)| *s <= t);
+        self.stops.insert(i, (t, color));
+        self
+    }
+
+    pub(crate) fn at(&self, p: Vec2) -> [f32; 4] {
+        let t = match self.spread {
+            Spread::Linear(a, b) => {
+                let d = b - a;
+                (p - a).dot(d) / d.length_squared().max(f32::EPSILON)
+            }
+            Spread::Radial(c, r) => (p - c).length() / r.max(f32::EPSILON),
+        }
+        .clamp(0., 1.);
+
+        let i = self.stops.partition_point(|(s, _)| *s <= t);
+        match (i.checked_sub(1).map(|i| self.stops[i]), self.stops.get(i).copied()) {
+            (Some((s0, c0)), Some((s1, c1))) => {
+                let k = (t - s0) / (s1 - s0).max(f32::EPSILON);
+                let (c0, c1) = (c0.get(), c1.get());
+                [0, 1, 2, 3].map(|j| c0[j] + (c1[j] - c0[j]) * k)
+            }
+            (Some((_, c)), None) | (None, Some((_, c))) => c.get(),
+            (None, None) => [1., 1., 1., 1.],
+        }
+    }
+}
+
+/// Путь из отрезков и кривых Безье, разбитых на ломаную.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Path {
+    points: Vec<Vec2>,
+    segments: usize,
+}
+
+impl Path {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self {
+            points: vec![vec2(x, y)],
+            segments: 16,
+        }
+    }
+
+    /// Число отрезков на каждую следующую кривую.
+    pub fn segments(mut self, n: usize) -> Self {
+        self.segments = n.max(1);
+        self
+    }
+
+    pub fn line_to(mut self, x: f32, y: f32) -> Self {
+        self.points.push(vec2(x, y));
+        self
+    }
+
+    /// Квадратичная кривая с контрольной точкой (cx, cy).
+    pub fn quad_to(mut self, cx: f32, cy: f32, x: f32, y: f32) -> Self {
+        let (from, c, to) = (self.last(), vec2(cx, cy), vec2(x, y));
+        for i in 1..=self.segments {
+            let t = i as f32 / self.segments as f32;
+            let u = 1. - t;
+            self.points.push(from * u * u + c * 2. * u * t + to * t * t);
+        }
+        self
+    }
+
+    /// Кубическая кривая с контрольными точками (c1x, c1y) и (c2x, c2y).
+    #[allow(clippy::too_many_arguments)]
+    pub fn cubic_to(mut self, c1x: f32, c1y: f32, c2x: f32, c2y: f32, x: f32, y: f32) -> Self {
+        let (from, c1, c2, to) = (self.last(), vec2(c1x, c1y), vec2(c2x, c2y), vec2(x, y));
+        for i in 1..=self.segments {
+            let t = i as f32 / self.segments as f32;
+            let u = 1. - t;
+            self.points.push(
+                from * u * u * u + c1 * 3. * u * u * t + c2 * 3. * u * t * t + to * t * t * t,
+            );
+        }
+        self
+    }
+
+    pub fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+
+    fn last(&self) -> Vec2 {
+        *self.points.last().unwrap()
+    }
+}
+
+/// Число отрезков полного круга, чтобы хорда отходила от дуги
+/// не больше чем на полпикселя. `radius` - радиус в пикселях экрана.
+pub(crate) fn segments(radius: f32) -> usize {
+    const TOLERANCE: f32 = 0.5;
+
+    if radius <= TOLERANCE {
+        return 6;
+    }
+
+    let step = 2. * (1. - TOLERANCE / radius).acos();
+    ((TAU / step).ceil() as usize).clamp(6, 256)
+}
+
+/// Точки замкнутого эллипса с полуосями `r`.
+pub(crate) fn ellipse(r: Vec2, segments: usize) -> Vec<Vec2> {
+    (0..segments)
+        .map(|i| {
+            let theta = i as f32 / segments as f32 * TAU;
+            vec2(theta.cos(), theta.sin()) * r
+        })
+        .collect()
+}
+
+/// Точки дуги от `start` до `end` включительно, отрезков не меньше одного.
+pub(crate) fn arc(r: f32, start: f32, end: f32, segments: usize) -> Vec<Vec2> {
+    let sweep = end - start;
+    let n = ((segments as f32 * sweep.abs() / TAU).ceil() as usize).max(1);
+
+    (0..=n)
+        .map(|i| {
+            let theta = start + sweep * i as f32 / n as f32;
+            vec2(theta.cos(), theta.sin()) * r
+        })
+        .collect()
+}
+
+/// Контур с рёбрами не длиннее 1/64 периметра.
+pub(crate) fn densify(points: &[Vec2], closed: bool) -> Vec<Vec2> {
+    let n = points.len();
+    let edges = if closed { n } else { n.saturating_sub(1) };
+    let perimeter: f32 = (0..edges).map(|i| points[i].distance(points[(i + 1) % n])).sum();
+    let step = perimeter / 64.;
+
+    let mut out = Vec::with_capacity(n);
+    for i in 0..n {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        out.push(a);
+
+        if i < edges && step > 0. {
+            let k = (a.distance(b) / step).ceil() as usize;
+            out.extend((1..k).map(|j| a.lerp(b, j as f32 / k as f32)));
+        }
+    }
+    out
+}
+
+/// Делит каждый треугольник на четыре по серединам сторон,
+/// пока фигура влезает в индексы u16, но не больше трёх раз.
+pub(crate) fn subdivide((points, indices): (Vec<Vec2>, Vec<u32>)) -> (Vec<Vec2>, Vec<u32>) {
+    let mut triangles: Vec<[Vec2; 3]> = indices
+        .chunks(3)
+        .map(|t| [points[t[0] as usize], points[t[1] as usize], points[t[2] as usize]])
+        .collect();
+
+    for _ in 0..3 {
+        if triangles.len() * 4 * 3 > u16::MAX as usize / 2 {
+            break;
+        }
+
+        triangles = triangles
+            .into_iter()
+            .flat_map(|[a, b, c]| {
+                let (ab, bc, ca) = ((a + b) / 2., (b + c) / 2., (c + a) / 2.);
+                [[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let points: Vec<Vec2> = triangles.into_iter().flatten().collect();
+    let indices = (0..points.len() as u32).collect();
+    (points, indices)
+}
+
+/// Угол точки лежит внутри дуги от `start` до `end`.
+pub(crate) fn in_sweep(p: Vec2, start: f32, end: f32) -> bool {
+    let (from, sweep) = if end >= start { (start, end - start) } else { (end, start - end) };
+    sweep >= TAU || (p.y.atan2(p.x) - from).rem_euclid(TAU) <= sweep
+}
+
+/// Триангуляция простого многоугольника отсечением ушей.
+pub(crate) fn triangulate(points: &[Vec2]) -> Vec<u32> {
+    let n = points.len();
+    if n < 3 {
+        return Vec::new();
+    }
+
+    // Обход против часовой стрелки, чтобы выпуклость проверялась одним знаком
+    let area: f32 = (0..n)
+        .map(|i| points[i].perp_dot(points[(i + 1) % n]))
+        .sum();
+    let mut rest: Vec<usize> = if area > 0. {
+        (0..n).collect()
+    } else {
+        (0..n).rev().collect()
+    };
+
+    let mut indices = Vec::with_capacity((n - 2) * 3);
+    let mut miss = 0;
+    let mut i = 0;
+
+    while rest.len() > 3 {
+        let len = rest.len();
+        let (a, b, c) = (rest[(i + len - 1) % len], rest[i % len], rest[(i + 1) % len]);
+        let (pa, pb, pc) = (points[a], points[b], points[c]);
+
+        let turn = (pb - pa).perp_dot(pc - pb);
+
+        // Точка на прямой между соседями треугольника не даёт
+        if turn.abs() <= f32::EPSILON {
+            rest.remove(i % len);
+            i %= rest.len();
+            continue;
+        }
+
+        let ear = turn > 0.
+            && !rest
+                .iter()
+                .filter(|&&p| p != a && p != b && p != c)
+                .any(|&p| in_triangle(points[p], pa, pb, pc));
+
+        if ear {
+            indices.extend([a as u32, b as u32, c as u32]);
+            rest.remove(i % len);
+            miss = 0;
+        } else {
+            i += 1;
+            miss += 1;
+
+            // Вырожденный многоугольник: остаток веером
+            if miss > len {
+                break;
+            }
+        }
+        i %= rest.len();
+    }
+
+    for k in 1..rest.len() - 1 {
+        indices.extend([rest[0] as u32, rest[k] as u32, rest[k + 1] as u32]);
+    }
+
+    indices
+}
+
+fn in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let d1 = (b - a).perp_dot(p - a);
+    let d2 = (c - b).perp_dot(p - b);
+    let d3 = (a - c).perp_dot(p - c);
+    d1 >= 0. && d2 >= 0. && d3 >= 0.
+}
+
+/// Геометрия толстой линии через точки: вершины и индексы треугольников.
+/// Соединения и концы достраиваются на вершинах соседних отрезков.
+pub(crate) fn stroke(points: &[Vec2], line: &Line, closed: bool) -> (Vec<Vec2>, Vec<u32>) {
+    let mut points = points.to_vec();
+    points.dedup();
+    if closed && points.len() > 2 && points.first() == points.last() {
+        points.pop();
+    }
+
+    let mut out = (Vec::new(), Vec::new());
+    let n = points.len();
+    if n < 2 {
+        return out;
+    }
+
+    let hw = line.width / 2.;
+    let count = if closed { n } else { n - 1 };
+
+    // Отрезок i - вершины 4i..4i+4: начало и конец слева, конец и начало справа
+    for i in 0..count {
+        let (mut a, mut b) = (points[i], points[(i + 1) % n]);
+        let dir = (b - a).normalize();
+
+        if !closed && line.cap == Cap::Square {
+            if i == 0 {
+                a -= dir * hw;
+            }
+            if i == count - 1 {
+                b += dir * hw;
+            }
+        }
+
+        let normal = dir.perp() * hw;
+        let k = i as u32 * 4;
+        out.0.extend([a + normal, b + normal, b - normal, a - normal]);
+        out.1.extend([k, k + 1, k + 3, k + 1, k + 2, k + 3]);
+    }
+
+    let joins = if closed { 0..n } else { 1..n - 1 };
+    for i in joins {
+        let p = points[i];
+        let d0 = (p - points[(i + n - 1) % n]).normalize();
+        let d1 = (points[(i + 1) % n] - p).normalize();
+
+        // Внешняя сторона поворота
+        let side = if d1.dot(d0.perp()) > 0. { -1. } else { 1. };
+        let n0 = d0.perp() * side;
+        let n1 = d1.perp() * side;
+
+        // Внешние углы отрезков до и после точки
+        let (prev, next) = (((i + n - 1) % n) as u32 * 4, i as u32 * 4);
+        let (a, b) = if side > 0. { (prev + 1, next) } else { (prev + 2, next + 3) };
+        let c = vertex(&mut out, p);
+
+        match line.join {
+            Join::Round => fan(&mut out, c, a, b),
+            Join::Miter => {
+                let dir = (n0 + n1).normalize_or_zero();
+                let cos = dir.dot(n0);
+
+                if cos > 0.25 {
+                    let m = vertex(&mut out, p + dir * hw / cos);
+                    out.1.extend([c, a, m, c, m, b]);
+                } else {
+                    out.1.extend([c, a, b]);
+                }
+            }
+            Join::Bevel => out.1.extend([c, a, b]),
+        }
+    }
+
+    if !closed && line.cap == Cap::Round {
+        let last = (count as u32 - 1) * 4;
+
+        // Скругление от одного угла конца через кончик к другому
+        for (p, next, right, left) in [(points[0], points[1], 3, 0), (points[n - 1], points[n - 2], last + 1, last + 2)] {
+            let back = (p - next).normalize();
+            let c = vertex(&mut out, p);
+            let tip = vertex(&mut out, p + back * hw);
+            fan(&mut out, c, right, tip);
+            fan(&mut out, c, tip, left);
+        }
+    }
+
+    out
+}
+
+fn vertex(out: &mut (Vec<Vec2>, Vec<u32>), p: Vec2) -> u32 {
+    out.0.push(p);
+    out.0.len() as u32 - 1
+}
+
+/// Дуга-веер вокруг вершины `c` от вершины `a` до `b` по короткой стороне.
+fn fan(out: &mut (Vec<Vec2>, Vec<u32>), c: u32, a: u32, b: u32) {
+    let center = out.0[c as usize];
+    let (ra, rb) = (out.0[a as usize] - center, out.0[b as usize] - center);
+    let r = ra.length();
+    let start = ra.y.atan2(ra.x);
+    let mut delta = rb.y.atan2(rb.x) - start;
+    if delta > PI {
+        delta -= 2. * PI;
+    } else if delta < -PI {
+        delta += 2. * PI;
+    }
+
+    let steps = (delta.abs() / (PI / 8.)).ceil().max(1.) as usize;
+    let mut prev = a;
+    for i in 1..steps {
+        let angle = start + delta * i as f32 / steps as f32;
+        let next = vertex(out, center + vec2(angle.cos(), angle.sin()) * r);
+        out.1.extend([c, prev, next]);
+        prev = next;
+    }
+    out.1.extend([c, prev, b]);
+}
@@ -187,7 +187,7 @@ impl ApplicationHandler for WgpuRender {
 
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(unsafe { &RENDERS[0].0 }),
+            contents: bytemuck::cast_slice(unsafe { &RENDERS[0].as_ref().unwrap().vertices }),
             usage: wgpu::BufferUsages::VERTEX,
         });
 
@@ -279,3 +279,8 @@ pub(crate) fn render(name: &str) {
     let mut window_state = WgpuRender::new(name);
     let _ = event_loop.run_app(&mut window_state);
 }
+
+/// Текстуры этот бэкенд пока не загружает, читать нечего.
+pub(crate) fn read_texture(_id: usize) -> Option<(Vec<u8>, u32, u32)> {
+    None
+}
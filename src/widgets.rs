@@ -1,20 +1,24 @@
 use crate::object::Touch::*;
-use crate::prelude::{circle, rect, text, Font, Module, Node2d, Obj2d, Rgba, Touch}; // Key};
+use crate::prelude::{circle, nine_slice, rect, text, CreateNode2d, Font, Module, NineSlice, Node2d, Obj2d, Rgba, Touch}; // Key};
 
 use glam::{vec2, Vec2};
 
 struct Button;
 impl Module for Button {
     fn touch(&self, obj: &mut Node2d, _id: u64, touch: &Touch, _pos: Vec2) {
-        let mut color = match touch {
-            Relese => {
-                obj.set_hash("button", false);
-                0.1
-            }
-            _ => {
-                obj.set_hash("button", true);
-                0.0
-            }
+        let pressed = match touch {
+            Relese => false,
+            Press | Move => true,
+        };
+        obj.set_hash("button", pressed);
+
+        // Скин затемняется при нажатии, простой фон - чернеет
+        let skin = matches!(obj.obj, Obj2d::NineSlice(_));
+        let color = match (skin, pressed) {
+            (true, false) => 1.,
+            (true, true) => 0.75,
+            (false, false) => 0.1,
+            (false, true) => 0.,
         };
 
         obj.color.r = color;
@@ -22,7 +26,7 @@ impl Module for Button {
         obj.color.b = color;
 
         if let Some(text) = obj.get_node("text") {
-            color = 0.9 + color;
+            let color = if pressed { 0.9 } else { 1. };
 
             text.color.r = color;
             text.color.g = color;
@@ -33,11 +37,7 @@ impl Module for Button {
 
 pub fn button(name: &str, tex: &str, size: f32, font: &Font) -> Node2d {
     let tex = text("text", &tex, size, &font);
-    let mut size = vec2(0., 0.);
-
-    if let Obj2d::Text(_, _, _, ref t) = tex.node2d.obj {
-        size = vec2(t.width, t.height)
-    }
+    let size = text_size(&tex);
 
     rect(&name, size.x + size.y, size.y * 2., size.x.min(size.y) / 4.)
         .color(Rgba::new(0.1, 0.1, 0.1, 1.))
@@ -47,6 +47,26 @@ pub fn button(name: &str, tex: &str, size: f32, font: &Font) -> Node2d {
         .get_node()
 }
 
+/// Кнопка с фоном из nine-slice.
+pub fn button_skin(name: &str, tex: &str, size: f32, font: &Font, skin: &NineSlice) -> Node2d {
+    let tex = text("text", &tex, size, &font);
+    let size = text_size(&tex);
+
+    nine_slice(name, skin, size.x + size.y, size.y * 2.)
+        .node(vec![tex])
+        .script(Button)
+        .hash("button", false)
+        .get_node()
+}
+
+fn text_size(tex: &CreateNode2d) -> Vec2 {
+    if let Obj2d::Text(_, _, _, ref t) = tex.node2d.obj {
+        vec2(t.width, t.height)
+    } else {
+        Vec2::ZERO
+    }
+}
+
 struct Check;
 impl Module for Check {
     fn update(&self, obj: &mut Node2d, d: f32) {
@@ -59,8 +79,16 @@ impl Module for Check {
 
                 if button.position.x != point {
                     button.position.x = (button.position.x + (point * d * 20.)).clamp(-size, size);
-                    let color = 0.5 + button.position.x / size * 0.4;
-                    obj.color.r = color;
+                    let t = button.position.x / size;
+
+                    if let Obj2d::NineSlice(_) = obj.obj {
+                        let color = 0.85 + t * 0.15;
+                        obj.color.r = color;
+                        obj.color.g = color;
+                        obj.color.b = color;
+                    } else {
+                        obj.color.r = 0.5 + t * 0.4;
+                    }
                 }
                 //button.position.x
             }
@@ -94,6 +122,18 @@ pub fn check(name: &str, size: f32) -> Node2d {
         .get_node() //.hash("posx", 0.)
 }
 
+/// Переключатель с фоном из nine-slice.
+pub fn check_skin(name: &str, size: f32, skin: &NineSlice) -> Node2d {
+    let size2 = size / 2.;
+
+    nine_slice(name, skin, size * 2. + size2, size + size2)
+        .color(Rgba::new(0.7, 0.7, 0.7, 1.))
+        .node(vec![circle("button", size / 2.)])
+        .script(Check)
+        .hash("check", false)
+        .get_node()
+}
+
 /*struct EditText;
 impl Module for EditText {
     fn touch(&self, obj: &mut Node2d, _id: u64, touch: &crate::engine::Touch, _pos: glam::Vec2) {